@@ -0,0 +1,711 @@
+use crate::{
+    config::{Config, When},
+    content_hash::ContentHashes,
+    reexec::check_not_nested,
+    rename::Renames,
+    watch::{
+        command, print_banner, should_reload_config, triggers_run, watch_config_dirs, Observer,
+        WatchdogEvent,
+    },
+};
+use futures_util::{stream, Stream};
+use notify::{
+    Config as NotifyConfig, Error, Event, RecommendedWatcher, RecursiveMode, Result, Watcher,
+};
+use std::{
+    io, mem,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    process::{Child, Command},
+    sync::mpsc::{unbounded_channel, UnboundedReceiver},
+    task::{spawn_blocking, JoinHandle},
+    time::{sleep_until, Instant},
+};
+use tokio_util::sync::CancellationToken;
+
+// how long a rename-from waits for its rename-to before being taken as a move out of the root
+const RENAME_TIMEOUT: Duration = Duration::from_millis(100);
+
+// the changes that triggered a run, once no more came in for the debounce time
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Batch {
+    paths: Vec<PathBuf>,
+    reload_config: bool,
+    // some of the paths aren't config files, so the command restarts whatever the reload says
+    other_changes: bool,
+}
+
+impl Batch {
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    // a config file changed, so the config should be reloaded rather than the command restarted
+    pub fn reload_config(&self) -> bool {
+        self.reload_config
+    }
+
+    // files other than the config changed as well
+    pub fn other_changes(&self) -> bool {
+        self.other_changes
+    }
+
+    fn add(&mut self, event: &Event, reload_config: bool) {
+        for path in &event.paths {
+            if !self.paths.contains(path) {
+                self.paths.push(path.clone());
+            }
+        }
+        self.reload_config |= reload_config;
+        self.other_changes |= !reload_config;
+    }
+}
+
+// the events of a rename pairing with whether they trigger a run, and the hashes it updated
+type Filtered = (Vec<(Event, bool)>, ContentHashes);
+
+// the file events of the root project, filtered by the config and debounced into batches
+pub struct Batches {
+    config: Arc<Config>,
+    watcher: RecommendedWatcher,
+    rx: UnboundedReceiver<Result<Event>>,
+    renames: Renames,
+    // moved into `filtering` while it runs, as hashing the changed files blocks
+    content_hashes: ContentHashes,
+    filtering: Option<JoinHandle<Filtered>>,
+    // kept across `next` calls, so that it can be cancelled without losing changes
+    pending: Option<(Batch, Instant)>,
+}
+
+impl Batches {
+    pub fn new(config: Config) -> Result<Self> {
+        let (tx, rx) = unbounded_channel();
+        let mut watcher = RecommendedWatcher::new(
+            move |res| {
+                let _ = tx.send(res);
+            },
+            NotifyConfig::default().with_compare_contents(true),
+        )?;
+        watcher.watch(config.root(), RecursiveMode::Recursive)?;
+        Ok(Self::with_events(config, watcher, rx))
+    }
+
+    // the events come from `rx`, fed by the watcher except in tests
+    fn with_events(
+        config: Config,
+        mut watcher: RecommendedWatcher,
+        rx: UnboundedReceiver<Result<Event>>,
+    ) -> Self {
+        watch_config_dirs(&config, |dir| {
+            watcher.watch(dir, RecursiveMode::NonRecursive)
+        });
+
+        Self {
+            content_hashes: ContentHashes::new(&config),
+            config: Arc::new(config),
+            watcher,
+            rx,
+            renames: Renames::default(),
+            filtering: None,
+            pending: None,
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    // e.g after reloading it, the following events are filtered with the new config
+    pub async fn set_config(&mut self, config: Config) {
+        // the events being filtered were received with the previous config
+        if let Some(filtering) = self.filtering.take() {
+            let filtered = filtering.await.expect("filtering the file events panicked");
+            self.add(filtered);
+        }
+
+        self.config = Arc::new(config);
+        if self.config.to_watch().hash_contents() {
+            let config = self.config.clone();
+            let mut content_hashes = mem::take(&mut self.content_hashes);
+            self.content_hashes = spawn_blocking(move || {
+                content_hashes.prefill(&config);
+                content_hashes
+            })
+            .await
+            .expect("hashing the files panicked");
+        }
+        // a reloaded config may extend files that weren't watched so far
+        watch_config_dirs(&self.config, |dir| {
+            self.watcher.watch(dir, RecursiveMode::NonRecursive)
+        });
+    }
+
+    // cancel safe, `None` once the file watcher stopped
+    pub async fn next(&mut self) -> Option<Batch> {
+        loop {
+            let deadline = self.pending.as_ref().map(|(_, deadline)| *deadline);
+            let flush = self
+                .renames
+                .is_pending()
+                .then(|| Instant::now() + RENAME_TIMEOUT);
+            // one pairing is filtered at a time, so that the events keep their order
+            let filtering = self.filtering.is_some();
+            let res = tokio::select! {
+                _ = sleep_until_some(deadline) => return self.pending.take().map(|(batch, _)| batch),
+                filtered = join_some(&mut self.filtering) => {
+                    self.filtering = None;
+                    self.add(filtered);
+                    continue;
+                }
+                _ = sleep_until_some(flush), if !filtering => {
+                    if let Some(event) = self.renames.flush() {
+                        self.filter(vec![event]);
+                    }
+                    continue;
+                }
+                res = self.rx.recv(), if !filtering => res,
+            };
+            let Some(res) = res else {
+                return self.pending.take().map(|(batch, _)| batch);
+            };
+
+            match res {
+                Ok(event) => {
+                    let events = self.renames.pair(event);
+                    if !events.is_empty() {
+                        self.filter(events);
+                    }
+                }
+                Err(err) => eprintln!("Watch error: {err:?}"),
+            }
+        }
+    }
+
+    // off the runtime, as telling whether an event triggers a run may hash the changed files
+    fn filter(&mut self, events: Vec<Event>) {
+        let config = self.config.clone();
+        let mut content_hashes = mem::take(&mut self.content_hashes);
+        self.filtering = Some(spawn_blocking(move || {
+            let events = events
+                .into_iter()
+                .map(|event| {
+                    let triggered = triggers_run(&config, &event, None, &mut content_hashes);
+                    (event, triggered)
+                })
+                .collect();
+            (events, content_hashes)
+        }));
+    }
+
+    fn add(&mut self, (events, content_hashes): Filtered) {
+        self.content_hashes = content_hashes;
+        for (event, _) in events.iter().filter(|(_, triggered)| *triggered) {
+            let reload_config = should_reload_config(&self.config, event);
+            let debounce = self.config.to_watch().debounce();
+            let (batch, _) = self
+                .pending
+                .get_or_insert_with(|| (Batch::default(), Instant::now() + debounce));
+            batch.add(event, reload_config);
+        }
+    }
+
+    pub fn into_stream(self) -> impl Stream<Item = Batch> {
+        stream::unfold(self, |mut batches| async move {
+            let batch = batches.next().await?;
+            Some((batch, batches))
+        })
+    }
+}
+
+// the same as Watchdog, on a tokio runtime: children are tokio processes, and it stops when its
+// cancellation token is cancelled
+pub struct AsyncWatchdog {
+    config: Config,
+    observers: Vec<Box<dyn Observer>>,
+    cancel: CancellationToken,
+}
+
+impl AsyncWatchdog {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            observers: Vec::new(),
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    pub fn observe(mut self, observer: impl Observer + 'static) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    // cancelling it stops the running command, then returns from `run`
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    // runs the command and restarts it on every change until cancelled
+    pub async fn run(self) -> Result<()> {
+        check_not_nested(self.config.root()).map_err(|err| Error::generic(&err))?;
+
+        let mut runner = Runner {
+            batches: Batches::new(self.config)?,
+            observers: self.observers,
+            child: None,
+            cancel: self.cancel,
+        };
+        runner.run().await
+    }
+}
+
+// the config is owned by the batches while running, as they filter file events with it
+struct Runner {
+    batches: Batches,
+    observers: Vec<Box<dyn Observer>>,
+    child: Option<RunningCommand>,
+    cancel: CancellationToken,
+}
+
+struct RunningCommand {
+    command: String,
+    child: Child,
+}
+
+impl Runner {
+    async fn run(&mut self) -> Result<()> {
+        self.exec(false).await;
+
+        loop {
+            tokio::select! {
+                _ = self.cancel.cancelled() => {
+                    self.stop().await;
+                    return Ok(());
+                }
+                status = wait(&mut self.child) => {
+                    let running = self.child.take().unwrap();
+                    self.emit(WatchdogEvent::CommandExited {
+                        command: running.command,
+                        status,
+                        pre: false,
+                    });
+                }
+                batch = self.batches.next() => {
+                    let Some(batch) = batch else {
+                        self.stop().await;
+                        return Err(Error::generic("the file watcher stopped"));
+                    };
+                    self.handler(batch).await;
+                }
+            }
+        }
+    }
+
+    fn config(&self) -> &Config {
+        self.batches.config()
+    }
+
+    fn emit(&mut self, event: WatchdogEvent) {
+        for observer in &mut self.observers {
+            observer.notify(&event);
+        }
+    }
+
+    async fn handler(&mut self, batch: Batch) {
+        self.emit(WatchdogEvent::BatchReceived {
+            paths: batch.paths().to_vec(),
+        });
+
+        if batch.reload_config() {
+            return self.reload_config(batch.other_changes()).await;
+        }
+
+        self.stop().await;
+        self.exec(true).await;
+    }
+
+    // `restart` when files other than the config changed in the same batch
+    async fn reload_config(&mut self, restart: bool) {
+        let config = self.batches.config.clone();
+        let new_config = spawn_blocking(move || config.reload().map_err(|err| err.to_string()))
+            .await
+            .expect("reloading the config panicked");
+        let new_config = match new_config {
+            Ok(new_config) => new_config,
+            Err(err) => {
+                eprintln!("Error loading new config, keeping the previous one:\n{err}");
+                if restart {
+                    self.stop().await;
+                    self.exec(true).await;
+                }
+                return;
+            }
+        };
+
+        let changes = self.config().changes(&new_config);
+        self.batches.set_config(new_config).await;
+
+        self.emit(WatchdogEvent::ConfigReloaded {
+            restart: changes.requires_restart(),
+        });
+        if !changes.requires_restart() && !restart {
+            println!("watchdog.toml reloaded, nothing to restart");
+            return;
+        }
+
+        self.stop().await;
+        // a changed exec_pre is run as if watchdog just started, including `once` commands
+        self.exec(!changes.exec_pre()).await;
+    }
+
+    async fn exec(&mut self, restart: bool) {
+        if self.config().to_watch().clear() {
+            let _ = Command::new("clear").status().await;
+        }
+        if !restart {
+            print_banner(self.config());
+        }
+
+        if let Some(exec_pre) = self.config().to_exec_pre() {
+            let run = match exec_pre.when() {
+                When::Once => !restart,
+                When::Always => true,
+            };
+            if run && !self.execute_pre().await {
+                return;
+            }
+        }
+
+        let exec = self.config().to_exec();
+        let (command, args) = (exec.command().to_owned(), exec.args().to_vec());
+        println!(
+            "executing command {:?} with args {:?} at origin {:?}",
+            command,
+            args,
+            exec.origin()
+        );
+        let child = spawn(&command, &args, exec.origin(), self.config().root()).ok();
+
+        if let Some(child) = child {
+            self.child = Some(RunningCommand {
+                command: command.clone(),
+                child,
+            });
+            self.emit(WatchdogEvent::CommandStarted {
+                command,
+                args,
+                pre: false,
+            });
+        }
+    }
+
+    // false if cancelled while running them
+    async fn execute_pre(&mut self) -> bool {
+        let Some(exec_pre) = self.config().to_exec_pre() else {
+            return true;
+        };
+        // taken out of the config so that the events can be emitted while the commands run
+        let origin = exec_pre.origin().clone();
+        let commands: Vec<(String, Vec<String>)> = exec_pre
+            .commands()
+            .iter()
+            .map(|command| (command.command().to_owned(), command.args().to_vec()))
+            .collect();
+
+        for (command, args) in commands {
+            println!(
+                "pre executing command {:?} with args {:?} at origin {:?}",
+                command, args, origin
+            );
+            let mut child = match spawn(&command, &args, &origin, self.config().root()) {
+                Ok(child) => child,
+                Err(err) => {
+                    eprintln!(
+                        "Something went wrong when executing command {command:?} with args {args:?}, skipping it. {err}"
+                    );
+                    continue;
+                }
+            };
+            self.emit(WatchdogEvent::CommandStarted {
+                command: command.clone(),
+                args,
+                pre: true,
+            });
+
+            let (status, cancelled) = tokio::select! {
+                status = child.wait() => (status.ok(), false),
+                _ = self.cancel.cancelled() => {
+                    let _ = child.start_kill();
+                    (child.wait().await.ok(), true)
+                }
+            };
+            self.emit(WatchdogEvent::CommandExited {
+                command,
+                status,
+                pre: true,
+            });
+            if cancelled {
+                return false;
+            }
+        }
+        true
+    }
+
+    async fn stop(&mut self) {
+        if let Some(mut running) = self.child.take() {
+            let _ = running.child.start_kill();
+            let status = running.child.wait().await.ok();
+            self.emit(WatchdogEvent::CommandExited {
+                command: running.command,
+                status,
+                pre: false,
+            });
+        }
+    }
+}
+
+// never resolves without a deadline, so that it can be raced against the other branches
+async fn sleep_until_some(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+// never resolves without events being filtered, so that it can be raced against the other branches
+async fn join_some(filtering: &mut Option<JoinHandle<Filtered>>) -> Filtered {
+    match filtering {
+        Some(handle) => handle.await.expect("filtering the file events panicked"),
+        None => std::future::pending().await,
+    }
+}
+
+// never resolves without a running command, so that it can be raced against the other branches
+async fn wait(child: &mut Option<RunningCommand>) -> Option<ExitStatus> {
+    match child {
+        Some(running) => running.child.wait().await.ok(),
+        None => std::future::pending().await,
+    }
+}
+
+fn spawn(program: &str, args: &[String], origin: &Path, root: &Path) -> io::Result<Child> {
+    Command::from(command(program, args, origin, root))
+        .kill_on_drop(true)
+        .spawn()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Batches, Runner};
+    use crate::{
+        config::Config,
+        testing::{create, modify, Recorder},
+        watch::{Observer, WatchdogEvent},
+    };
+    use notify::{Config as NotifyConfig, Event, RecommendedWatcher, Result, Watcher};
+    use std::{fs, time::Duration};
+    use tempfile::TempDir;
+    use tokio::{
+        sync::mpsc::{unbounded_channel, UnboundedSender},
+        time::{sleep, timeout, Instant},
+    };
+    use tokio_util::sync::CancellationToken;
+
+    const DEBOUNCE: Duration = Duration::from_millis(500);
+
+    fn config(contents: &str) -> (TempDir, Config) {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("watchdog.toml"), contents).unwrap();
+        fs::write(root.path().join("a.rs"), "").unwrap();
+        fs::write(root.path().join("b.rs"), "").unwrap();
+        let config = Config::builder().root(root.path()).build().unwrap();
+        (root, config)
+    }
+
+    // batches fed by the test rather than by the file watcher
+    fn batches(config: Config) -> (UnboundedSender<Result<Event>>, Batches) {
+        let (tx, rx) = unbounded_channel();
+        let watcher =
+            RecommendedWatcher::new(|_: Result<Event>| {}, NotifyConfig::default()).unwrap();
+        (tx, Batches::with_events(config, watcher, rx))
+    }
+
+    fn runner(
+        batches: Batches,
+        cancel: CancellationToken,
+        observers: [Box<dyn Observer>; 2],
+    ) -> Runner {
+        Runner {
+            batches,
+            observers: observers.into(),
+            child: None,
+            cancel,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn debounces_from_the_first_event() {
+        let (_root, config) = config("[exec]\ncommand = \"sleep\"\n");
+        let (a, b) = (config.root().join("a.rs"), config.root().join("b.rs"));
+        let (tx, mut batches) = batches(config);
+        let start = Instant::now();
+
+        tx.send(Ok(create(&a))).unwrap();
+        let (batch, _) = tokio::join!(batches.next(), async {
+            sleep(DEBOUNCE / 2).await;
+            tx.send(Ok(modify(&b))).unwrap();
+        });
+
+        assert_eq!(start.elapsed(), DEBOUNCE);
+        assert_eq!(batch.unwrap().paths(), [a, b]);
+        drop(tx);
+        assert_eq!(batches.next().await, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keeps_the_pending_batch_when_cancelled() {
+        let (_root, config) = config("[exec]\ncommand = \"sleep\"\n");
+        let (a, b) = (config.root().join("a.rs"), config.root().join("b.rs"));
+        let (tx, mut batches) = batches(config);
+        let start = Instant::now();
+
+        tx.send(Ok(create(&a))).unwrap();
+        assert!(timeout(DEBOUNCE / 5, batches.next()).await.is_err());
+        tx.send(Ok(create(&b))).unwrap();
+        let batch = batches.next().await.unwrap();
+
+        assert_eq!(start.elapsed(), DEBOUNCE);
+        assert_eq!(batch.paths(), [a, b]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tells_config_reloads_from_other_changes() {
+        let (_root, config) = config("[exec]\ncommand = \"sleep\"\n");
+        let (a, config_path) = (
+            config.root().join("a.rs"),
+            config.root().join("watchdog.toml"),
+        );
+        let (tx, mut batches) = batches(config);
+
+        fs::write(&config_path, "[exec]\ncommand = \"true\"\n").unwrap();
+        tx.send(Ok(modify(&config_path))).unwrap();
+        let reload = batches.next().await.unwrap();
+        fs::write(&config_path, "[exec]\ncommand = \"sleep\"\n").unwrap();
+        tx.send(Ok(modify(&config_path))).unwrap();
+        tx.send(Ok(create(&a))).unwrap();
+        let reload_and_restart = batches.next().await.unwrap();
+        tx.send(Ok(create(&a))).unwrap();
+        let restart = batches.next().await.unwrap();
+
+        assert!(reload.reload_config() && !reload.other_changes());
+        assert!(reload_and_restart.reload_config() && reload_and_restart.other_changes());
+        assert!(!restart.reload_config() && restart.other_changes());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stops_when_cancelled_during_the_pre_commands() {
+        let (_root, config) = config(
+            "[exec]\ncommand = \"sleep\"\nargs = [ \"30\" ]\n\n[exec_pre]\ncommands = [ { command = \"sleep\", args = [ \"30\" ] }, { command = \"true\" } ]\n\n[watch]\nclear = false\n",
+        );
+        let (_tx, batches) = batches(config);
+        let recorder = Recorder::new();
+        let cancel = CancellationToken::new();
+        let cancel_on_start = {
+            let cancel = cancel.clone();
+            move |event: &WatchdogEvent| {
+                if let WatchdogEvent::CommandStarted { .. } = event {
+                    cancel.cancel();
+                }
+            }
+        };
+        let observers: [Box<dyn Observer>; 2] =
+            [Box::new(recorder.clone()), Box::new(cancel_on_start)];
+        let mut runner = runner(batches, cancel, observers);
+
+        // the pre command is reported while it runs, otherwise it's never cancelled
+        timeout(Duration::from_secs(10), runner.run())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let events = recorder.events();
+        assert_eq!(events.len(), 2, "{events:?}");
+        assert!(matches!(
+            &events[0],
+            WatchdogEvent::CommandStarted { command, pre: true, .. } if command == "sleep"
+        ));
+        assert!(matches!(
+            &events[1],
+            WatchdogEvent::CommandExited { status: Some(status), pre: true, .. } if !status.success()
+        ));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn restarts_for_the_other_changes_of_a_config_reload() {
+        let (_root, config) = config(
+            "[exec]\ncommand = \"sleep\"\nargs = [ \"30\" ]\n\n[watch]\nclear = false\ndebounce = 10\n",
+        );
+        let (a, config_path) = (
+            config.root().join("a.rs"),
+            config.root().join("watchdog.toml"),
+        );
+        let (tx, batches) = batches(config);
+        let recorder = Recorder::new();
+        let cancel = CancellationToken::new();
+        // edits the config along with another file once started, then stops once restarted
+        let driver = {
+            let (cancel, mut started) = (cancel.clone(), 0);
+            let (a, config_path) = (a.clone(), config_path.clone());
+            move |event: &WatchdogEvent| {
+                if let WatchdogEvent::CommandStarted { .. } = event {
+                    started += 1;
+                    if started == 1 {
+                        fs::write(
+                            &config_path,
+                            "[exec]\ncommand = \"sleep\"\nargs = [ \"30\" ]\n\n[watch]\nclear = false\ndebounce = 20\n",
+                        )
+                        .unwrap();
+                        tx.send(Ok(modify(&config_path))).unwrap();
+                        tx.send(Ok(create(&a))).unwrap();
+                    } else {
+                        cancel.cancel();
+                    }
+                }
+            }
+        };
+        let observers: [Box<dyn Observer>; 2] = [Box::new(recorder.clone()), Box::new(driver)];
+        let mut runner = runner(batches, cancel, observers);
+
+        timeout(Duration::from_secs(10), runner.run())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let events = recorder.events();
+        assert_eq!(events.len(), 6, "{events:?}");
+        assert_eq!(
+            events[1],
+            WatchdogEvent::BatchReceived {
+                paths: vec![config_path, a]
+            }
+        );
+        assert_eq!(events[2], WatchdogEvent::ConfigReloaded { restart: false });
+        assert!(matches!(
+            events[3],
+            WatchdogEvent::CommandExited { pre: false, .. }
+        ));
+        assert!(matches!(
+            events[4],
+            WatchdogEvent::CommandStarted { pre: false, .. }
+        ));
+    }
+}
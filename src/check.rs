@@ -0,0 +1,268 @@
+use crate::{
+    config::{ArgsOpt, ArgsOptCheck, Config},
+    watch::ignore_reason,
+};
+use std::{
+    fmt::{Display, Write},
+    path::{Component, Path, PathBuf},
+};
+
+pub fn check(args_opt: ArgsOpt, mut args_check: ArgsOptCheck) -> i32 {
+    let config = match Config::new(args_opt) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            return 1;
+        }
+    };
+
+    print!("{}", render_config(&config));
+
+    let paths = args_check.take_paths();
+    if !paths.is_empty() {
+        println!();
+        print!("{}", render_paths(&config, paths));
+    }
+
+    let problems = problems(&config);
+    if problems.is_empty() {
+        return 0;
+    }
+
+    eprintln!();
+    for problem in &problems {
+        eprintln!("error: {problem}");
+    }
+    1
+}
+
+// written to a string rather than to stdout so that it can be tested
+fn render_config(config: &Config) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "root: {:?}", config.root());
+    if let Some(profile) = config.profile() {
+        let _ = writeln!(out, "profile: {profile}");
+    }
+    let _ = writeln!(out, "config files:");
+    for config_file in config.config_files() {
+        let state = if config_file.exists() {
+            ""
+        } else {
+            " (missing)"
+        };
+        let _ = writeln!(out, "  {config_file:?}{state}");
+    }
+    let _ = writeln!(out);
+
+    let exec = config.to_exec();
+    print_value(
+        &mut out,
+        config,
+        "exec.command",
+        format!("{:?}", exec.command()),
+    );
+    print_value(&mut out, config, "exec.args", format!("{:?}", exec.args()));
+    print_value(
+        &mut out,
+        config,
+        "exec.origin",
+        format!("{:?}", exec.origin()),
+    );
+    print_value(&mut out, config, "exec.interactive", exec.interactive());
+
+    if let Some(exec_pre) = config.to_exec_pre() {
+        let commands: Vec<String> = exec_pre
+            .commands()
+            .iter()
+            .map(|exec_pre_command| {
+                [exec_pre_command.command()]
+                    .into_iter()
+                    .chain(exec_pre_command.args().iter().map(String::as_str))
+                    .collect::<Vec<&str>>()
+                    .join(" ")
+            })
+            .collect();
+        print_value(
+            &mut out,
+            config,
+            "exec_pre.commands",
+            format!("{commands:?}"),
+        );
+        print_value(&mut out, config, "exec_pre.when", exec_pre.when());
+        print_value(
+            &mut out,
+            config,
+            "exec_pre.origin",
+            format!("{:?}", exec_pre.origin()),
+        );
+    }
+
+    print_value(
+        &mut out,
+        config,
+        "exclude.files",
+        format!("{:?}", config.to_exclude().to_exclude_files()),
+    );
+    let extendables: Vec<String> = config
+        .to_extend()
+        .to_extendables()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    print_value(
+        &mut out,
+        config,
+        "extend.extendables",
+        format!("{extendables:?}"),
+    );
+
+    let watch = config.to_watch();
+    let events: Vec<String> = watch.events().iter().map(ToString::to_string).collect();
+    print_value(&mut out, config, "watch.events", format!("{events:?}"));
+    print_value(
+        &mut out,
+        config,
+        "watch.hash_contents",
+        watch.hash_contents(),
+    );
+    print_value(&mut out, config, "watch.reexec", watch.reexec());
+    print_value(&mut out, config, "watch.clear", watch.clear());
+    print_value(
+        &mut out,
+        config,
+        "watch.debounce",
+        format!("{}ms", watch.debounce().as_millis()),
+    );
+    print_value(&mut out, config, "watch.keys", watch.keys());
+    print_value(&mut out, config, "watch.escape_key", watch.escape_key());
+    print_value(
+        &mut out,
+        config,
+        "watch.run_on_resume",
+        watch.run_on_resume(),
+    );
+    out
+}
+
+fn print_value(out: &mut String, config: &Config, key: &str, value: impl Display) {
+    let _ = writeln!(
+        out,
+        "{key:<20} = {:<40} ({})",
+        value.to_string(),
+        config.sources().get(key)
+    );
+}
+
+fn render_paths(config: &Config, paths: Vec<PathBuf>) -> String {
+    let mut out = String::from("paths:\n");
+    for path in paths {
+        let absolute_path = normalise(&config.root().join(&path));
+        let verdict = if config.config_files().contains(&absolute_path) {
+            "reloads the config".to_owned()
+        } else if !absolute_path.starts_with(config.root()) {
+            "not watched: outside of the root project".to_owned()
+        } else {
+            match ignore_reason(config, &absolute_path, absolute_path.is_dir()) {
+                Some(reason) => format!("ignored: {reason}"),
+                None => "triggers a run".to_owned(),
+            }
+        };
+        let _ = writeln!(out, "  {path:?}: {verdict}");
+    }
+    out
+}
+
+fn problems(config: &Config) -> Vec<String> {
+    config
+        .to_extend()
+        .to_extendables()
+        .iter()
+        .filter_map(|extendable| extendable.problem(config.root()))
+        .collect()
+}
+
+// sample paths don't have to exist, so `..` is resolved without touching the file system
+fn normalise(path: &Path) -> PathBuf {
+    let mut normalised = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalised.pop();
+            }
+            Component::CurDir => {}
+            component => normalised.push(component),
+        }
+    }
+    normalised
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_config, render_paths};
+    use crate::config::Config;
+    use std::{fs, path::PathBuf};
+    use tempfile::TempDir;
+
+    fn project() -> (TempDir, Config) {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("watchdog.toml"),
+            "[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n\n[exclude]\nfiles = [ { path = \"target\" } ]\n\n[extend]\nextendables = [ { extendable_type = \"git\", path = \".gitignore\" } ]\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join(".gitignore"), "*.log\n/build/\n").unwrap();
+        let config = Config::builder().root(dir.path()).build().unwrap();
+        (dir, config)
+    }
+
+    #[test]
+    fn prints_the_effective_config_and_where_it_comes_from() {
+        let (_dir, config) = project();
+        let rendered = render_config(&config);
+
+        assert!(rendered.contains(&format!(
+            "config files:\n  {:?}\n",
+            config.root().join("watchdog.toml")
+        )));
+        let lines = [
+            ("exec.command", "\"cargo\"", "file"),
+            ("exec.args", "[\"run\"]", "file"),
+            ("exclude.files", "[\"target\"]", "file"),
+            ("extend.extendables", "[\"git \\\".gitignore\\\"\"]", "file"),
+            ("watch.debounce", "500ms", "default"),
+        ];
+        for (key, value, source) in lines {
+            let line = format!("{key:<20} = {value:<40} ({source})\n");
+            assert!(rendered.contains(&line), "{line}in\n{rendered}");
+        }
+    }
+
+    #[test]
+    fn tells_why_paths_are_ignored() {
+        let (_dir, config) = project();
+        let paths = [
+            "src/main.rs",
+            "target/debug/watchdog",
+            "debug.log",
+            "build/main.o",
+            "watchdog.toml",
+            "../other/main.rs",
+        ];
+        let rendered = render_paths(&config, paths.iter().map(PathBuf::from).collect());
+
+        assert_eq!(
+            rendered,
+            [
+                "paths:",
+                "  \"src/main.rs\": triggers a run",
+                "  \"target/debug/watchdog\": ignored: excluded by \"target\"",
+                "  \"debug.log\": ignored: pattern `*.log` in git ignore file \".gitignore\"",
+                "  \"build/main.o\": ignored: pattern `/build/` in git ignore file \".gitignore\"",
+                "  \"watchdog.toml\": reloads the config",
+                "  \"../other/main.rs\": not watched: outside of the root project",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+}
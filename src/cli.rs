@@ -0,0 +1,41 @@
+use crate::{
+    check::check,
+    config::{ArgsOpt, ArgsOptCommand, Config},
+    init::init,
+    migrate::migrate,
+    pause::{pause, resume},
+    schema::schema,
+    watch::Watchdog,
+};
+use clap::Parser;
+
+// the `watchdog` binary, returns the exit code
+pub fn run() -> i32 {
+    let mut args_opt = ArgsOpt::parse();
+    if let Some(command) = args_opt.take_command() {
+        return match command {
+            ArgsOptCommand::Check(args_check) => check(args_opt, args_check),
+            ArgsOptCommand::Init(args_init) => init(args_init),
+            ArgsOptCommand::Migrate => migrate(),
+            ArgsOptCommand::Pause => pause(),
+            ArgsOptCommand::Resume => resume(),
+            ArgsOptCommand::Schema => schema(),
+        };
+    }
+
+    let config = match Config::new(args_opt) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            return 1;
+        }
+    };
+
+    match Watchdog::new(config).keys().signals().run() {
+        Ok(stopped) => stopped.exit_code(),
+        Err(err) => {
+            eprintln!("{err}");
+            1
+        }
+    }
+}
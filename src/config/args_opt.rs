@@ -1,9 +1,13 @@
-use clap::{builder::ArgPredicate, Args, Parser, ValueEnum};
+use clap::{builder::ArgPredicate, Args, Parser, Subcommand, ValueEnum};
 use serde::Deserialize;
+use std::path::PathBuf;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Clone, Debug, Default)]
 #[command(version, about("A lightweight, language-agnostic file watcher that can execute commands when your code changes"))]
 pub struct ArgsOpt {
+    #[command(subcommand)]
+    command: Option<ArgsOptCommand>,
+
     #[command(flatten)]
     exec: Option<ArgsOptExec>,
 
@@ -20,9 +24,40 @@ pub struct ArgsOpt {
 
     #[command(flatten)]
     extend: Option<ArgsOptExtend>,
+
+    #[arg(
+        long,
+        visible_alias("no-config-excludes"),
+        help("drop the [exclude] and [extend] rules of the config files, instead of adding the CLI ones to them")
+    )]
+    replace_exclude: bool,
+
+    #[command(flatten)]
+    watch: Option<ArgsOptWatch>,
+
+    #[arg(
+        long,
+        global(true),
+        help("don't load the user-level config from $XDG_CONFIG_HOME/watchdog/config.toml")
+    )]
+    no_global_config: bool,
+
+    #[arg(
+        short,
+        long,
+        global(true),
+        env("WATCHDOG_PROFILE"),
+        value_name("NAME"),
+        help("the [profile.<name>] of watchdog.toml to use on top of the rest of it e.g watchdog --profile test")
+    )]
+    profile: Option<String>,
 }
 
 impl ArgsOpt {
+    pub fn take_command(&mut self) -> Option<ArgsOptCommand> {
+        self.command.take()
+    }
+
     pub fn take_exec(&mut self) -> Option<ArgsOptExec> {
         self.exec.take()
     }
@@ -38,6 +73,78 @@ impl ArgsOpt {
     pub fn take_extend(&mut self) -> Option<ArgsOptExtend> {
         self.extend.take()
     }
+
+    pub fn take_watch(&mut self) -> Option<ArgsOptWatch> {
+        self.watch.take()
+    }
+
+    pub fn replace_exclude(&self) -> bool {
+        self.replace_exclude
+    }
+
+    pub fn no_global_config(&self) -> bool {
+        self.no_global_config
+    }
+
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum ArgsOptCommand {
+    #[command(about(
+        "validate watchdog.toml, print the effective config and test paths against it"
+    ))]
+    Check(ArgsOptCheck),
+
+    #[command(about("create a watchdog.toml for the project type detected in the root project"))]
+    Init(ArgsOptInit),
+
+    #[command(about(
+        "rewrite the deprecated [run] and [build] tables of watchdog.toml as [exec] and [exec_pre]"
+    ))]
+    Migrate,
+
+    #[command(about(
+        "pause the watchdog running for the root project by creating .watchdog-pause in it"
+    ))]
+    Pause,
+
+    #[command(about("resume the paused watchdog by removing .watchdog-pause"))]
+    Resume,
+
+    #[command(about(
+        "print the JSON Schema of the config files, e.g for editors to validate them"
+    ))]
+    Schema,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct ArgsOptCheck {
+    #[arg(
+        value_name("PATH"),
+        help("paths relative to the root project to test against the config e.g watchdog check src/main.rs target/debug")
+    )]
+    paths: Vec<PathBuf>,
+}
+
+impl ArgsOptCheck {
+    pub fn take_paths(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.paths)
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct ArgsOptInit {
+    #[arg(short, long, help("overwrite an existing watchdog.toml"))]
+    force: bool,
+}
+
+impl ArgsOptInit {
+    pub fn force(&self) -> bool {
+        self.force
+    }
 }
 
 #[derive(Args, Clone, Debug)]
@@ -58,6 +165,12 @@ pub struct ArgsOptExec {
         help("a path relative to the root project to where to run exec")
     )]
     pub origin: Option<String>,
+
+    #[arg(
+        long,
+        help("pass what's typed in the terminal to the command, watchdog's keys then follow the escape key")
+    )]
+    pub interactive: bool,
 }
 
 impl ArgsOptExec {
@@ -68,6 +181,10 @@ impl ArgsOptExec {
     pub fn take_origin(&mut self) -> Option<String> {
         self.origin.take()
     }
+
+    pub fn interactive(&self) -> bool {
+        self.interactive
+    }
 }
 
 #[derive(Args, Clone, Debug)]
@@ -149,3 +266,86 @@ impl ArgsOptExtend {
         self.extendable_type.take()
     }
 }
+
+#[derive(Args, Clone, Debug)]
+pub struct ArgsOptWatch {
+    #[arg(
+        long,
+        value_name("EVENT"),
+        value_delimiter(','),
+        help("the kind of file events that trigger a run e.g watchdog --events create,modify:data,remove")
+    )]
+    events: Option<Vec<ArgsOptEventKind>>,
+
+    #[arg(
+        long,
+        help("re-execute watchdog itself whenever exec-pre rebuilds its binary e.g when watching watchdog's own project")
+    )]
+    reexec: bool,
+
+    #[arg(long, help("don't clear the screen before every run"))]
+    no_clear: bool,
+
+    #[arg(
+        long,
+        value_name("MS"),
+        help("how long to wait after a run is triggered, changes made in the meantime are discarded e.g watchdog --debounce 1000")
+    )]
+    debounce: Option<u64>,
+
+    #[arg(long, help("don't read keys from the terminal"))]
+    no_keys: bool,
+
+    #[arg(long, help("run once on resume if anything changed while paused"))]
+    run_on_resume: bool,
+
+    #[arg(
+        long,
+        value_name("KEY"),
+        help("the key preceding watchdog's keys when the command is interactive e.g watchdog --interactive --escape-key ctrl-t")
+    )]
+    escape_key: Option<String>,
+}
+
+impl ArgsOptWatch {
+    pub fn take_events(&mut self) -> Option<Vec<ArgsOptEventKind>> {
+        self.events.take()
+    }
+
+    pub fn reexec(&self) -> bool {
+        self.reexec
+    }
+
+    pub fn no_clear(&self) -> bool {
+        self.no_clear
+    }
+
+    pub fn take_debounce(&mut self) -> Option<u64> {
+        self.debounce.take()
+    }
+
+    pub fn no_keys(&self) -> bool {
+        self.no_keys
+    }
+
+    pub fn run_on_resume(&self) -> bool {
+        self.run_on_resume
+    }
+
+    pub fn take_escape_key(&mut self) -> Option<String> {
+        self.escape_key.take()
+    }
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug)]
+pub enum ArgsOptEventKind {
+    Create,
+    Remove,
+    Rename,
+    Modify,
+    #[value(name = "modify:data")]
+    ModifyData,
+    #[value(name = "modify:metadata")]
+    ModifyMetadata,
+    Access,
+}
@@ -0,0 +1,479 @@
+use super::{
+    extendable::{BoxedExtendable, ExtendableRegistry},
+    find_root, ArgsOpt, Config, ExecPreCommand, FileOpt, FileOptExclude, FileOptExec,
+    FileOptExecPre, FileOptExecPreCommand, FileOptExtend, FileOptExtendable, FileOptWhen, When,
+};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+// builds a Config from explicit inputs, e.g when embedding watchdog, without reading the process
+// arguments, nor the current directory or git when a root is given
+#[derive(Debug, Clone, Default)]
+pub struct ConfigBuilder {
+    root: Option<PathBuf>,
+    config_file: Option<PathBuf>,
+    global_config: bool,
+    global_config_file: Option<PathBuf>,
+    profile: Option<String>,
+    overrides: FileOpt,
+    // the Sources keys of the overrides
+    overridden: Vec<&'static str>,
+    args_opt: ArgsOpt,
+    extendables: ExtendableRegistry,
+}
+
+impl ConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // found with git or the current directory if not set
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    // a config file relative to the root project, instead of looking up watchdog.toml and co.
+    pub fn from_file(mut self, config_file: impl Into<PathBuf>) -> Self {
+        self.config_file = Some(config_file.into());
+        self
+    }
+
+    // whether to load $XDG_CONFIG_HOME/watchdog/config.toml under the project config, off by default
+    pub fn global_config(mut self, global_config: bool) -> Self {
+        self.global_config = global_config;
+        self
+    }
+
+    // loads this file as the global config instead, e.g to share one between several embedders
+    pub fn global_config_file(mut self, global_config_file: impl Into<PathBuf>) -> Self {
+        self.global_config = true;
+        self.global_config_file = Some(global_config_file.into());
+        self
+    }
+
+    pub fn profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    // the settings below go on top of the config files, the same way a profile does
+    pub fn exec(
+        mut self,
+        command: impl Into<String>,
+        args: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        let exec = FileOptExec::new(command.into(), args.into_iter().map(Into::into).collect());
+        self.overrides.layer(FileOpt::with_exec(exec));
+        self.overridden.extend(["exec.command", "exec.args"]);
+        self
+    }
+
+    pub fn exec_pre(
+        mut self,
+        when: When,
+        commands: impl IntoIterator<Item = ExecPreCommand>,
+    ) -> Self {
+        let when = match when {
+            When::Once => FileOptWhen::Once,
+            When::Always => FileOptWhen::Always,
+        };
+        let commands = commands
+            .into_iter()
+            .map(|command| FileOptExecPreCommand::new(command.command, command.args))
+            .collect();
+        self.overrides
+            .layer(FileOpt::with_exec_pre(FileOptExecPre::new(when, commands)));
+        self.overridden
+            .extend(["exec_pre.when", "exec_pre.commands"]);
+        self
+    }
+
+    // added to the [exclude] rules of the config files
+    pub fn exclude(mut self, paths: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let exclude = FileOptExclude::new(paths.into_iter().map(Into::into).collect());
+        self.overrides.layer(FileOpt::with_exclude(exclude));
+        self.overridden.push("exclude.files");
+        self
+    }
+
+    // git ignore files relative to the root project, added to the [extend] rules of the config files
+    pub fn extend(self, paths: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.extend_with("git", paths)
+    }
+
+    // the same for the ignore files of a matcher registered with `register_extendable`
+    pub fn extend_with(
+        mut self,
+        extendable_type: impl Into<String>,
+        paths: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        let extendable_type = extendable_type.into();
+        let extendables = paths
+            .into_iter()
+            .map(|path| FileOptExtendable::new(extendable_type.clone(), path.into()))
+            .collect();
+        self.overrides
+            .layer(FileOpt::with_extend(FileOptExtend::new(extendables)));
+        self.overridden.push("extend.extendables");
+        self
+    }
+
+    // a matcher [extend] can refer to with `extendable_type = "<name>"`, built from the root project
+    // and the path next to it, e.g for .bazelignore files or to ask an external tool
+    pub fn register_extendable(
+        mut self,
+        name: impl Into<String>,
+        factory: impl Fn(&Path, &Path) -> Result<BoxedExtendable, String> + Send + Sync + 'static,
+    ) -> Self {
+        self.extendables.register(name, factory);
+        self
+    }
+
+    pub(super) fn extendables(&self) -> &ExtendableRegistry {
+        &self.extendables
+    }
+
+    // CLI arguments go on top of everything else, and pick the profile and global config as well
+    pub(crate) fn args(mut self, args_opt: ArgsOpt) -> Self {
+        if let Some(profile) = args_opt.profile() {
+            self.profile = Some(profile.to_owned());
+        }
+        self.global_config = !args_opt.no_global_config();
+        self.args_opt = args_opt;
+        self
+    }
+
+    pub fn build(&self) -> Result<Config, Box<dyn Error>> {
+        let root = match &self.root {
+            Some(root) => fs::canonicalize(root)
+                .map_err(|err| format!("Could not use {root:?} as the root project: {err}"))?,
+            None => find_root()?,
+        };
+
+        let mut file_opt = match self.global_config {
+            true => FileOpt::parse_global(self.global_config_file.as_deref(), &root)?,
+            false => FileOpt::default(),
+        };
+        let project_opt = match &self.config_file {
+            Some(config_file) => FileOpt::parse_file(&root.join(config_file), &root)?,
+            None => FileOpt::parse(&root)?,
+        };
+        file_opt.layer(project_opt);
+        file_opt.select_profile(self.profile.as_deref(), &root)?;
+        let adds_to_file: Vec<bool> = self
+            .overridden
+            .iter()
+            .map(|key| file_opt.sets(key))
+            .collect();
+        file_opt.layer(self.overrides.clone());
+
+        let mut config = Config {
+            root,
+            profile: self.profile.clone(),
+            builder: self.clone(),
+            ..Config::default()
+        }
+        .merge(file_opt, self.args_opt.clone())?;
+        for (key, adds_to_file) in self.overridden.iter().zip(adds_to_file) {
+            config.sources.set_builder(key, adds_to_file);
+        }
+        config.canonicalise();
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConfigBuilder;
+    use crate::config::{ArgsOpt, Config, Extendable, Source, When};
+    use clap::Parser;
+    use std::{
+        env,
+        fmt::{Display, Formatter, Result as FmtResult},
+        fs,
+        path::{Path, PathBuf},
+        sync::Mutex,
+        time::Duration,
+    };
+
+    // the CLI arguments read environment variables as well, which are shared by every test
+    static ENV: Mutex<()> = Mutex::new(());
+
+    fn build_with_args(root: &Path, args: &[&str]) -> Config {
+        let args_opt = ArgsOpt::parse_from(["watchdog"].iter().chain(args));
+        ConfigBuilder::new()
+            .root(root)
+            .args(args_opt)
+            .build()
+            .unwrap()
+    }
+
+    #[derive(Debug)]
+    struct Prefix(PathBuf);
+
+    impl Extendable for Prefix {
+        fn matcher(&self, path: &Path, _is_dir: bool) -> bool {
+            path.starts_with(&self.0)
+        }
+    }
+
+    impl Display for Prefix {
+        fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+            write!(f, "prefix {:?}", self.0)
+        }
+    }
+
+    #[test]
+    fn builds_from_explicit_inputs() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(
+            root.path().join("watchdog.toml"),
+            "[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n\n[exclude]\nfiles = [ { path = \"target\" } ]\n",
+        )
+        .unwrap();
+        fs::write(root.path().join(".gitignore"), "*.log\n").unwrap();
+
+        let config = ConfigBuilder::new()
+            .root(root.path())
+            .exec("cargo", ["test"])
+            .exec_pre(When::Once, [crate::ExecPreCommand::new("cargo", ["build"])])
+            .exclude([".git"])
+            .extend([".gitignore"])
+            .build()
+            .unwrap();
+
+        assert_eq!(config.to_exec().command(), "cargo");
+        assert_eq!(config.to_exec().args(), &["test"]);
+        assert_eq!(config.to_exec_pre().as_ref().unwrap().when(), &When::Once);
+        assert_eq!(config.to_exclude().to_exclude_files(), &["target", ".git"]);
+        let sources = config.sources();
+        assert_eq!(sources.get("exec.args"), Source::Builder);
+        assert_eq!(sources.get("exec_pre.when"), Source::Builder);
+        assert_eq!(sources.get("exclude.files"), Source::FileAndBuilder);
+        assert_eq!(sources.get("extend.extendables"), Source::Builder);
+        assert_eq!(sources.get("watch.debounce"), Source::Default);
+    }
+
+    #[test]
+    fn builds_registered_extendables() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(
+            root.join("watchdog.toml"),
+            "[exec]\ncommand = \"cargo\"\n\n[extend]\nextendables = [ { extendable_type = \"prefix\", path = \"bazel-out\" } ]\n",
+        )
+        .unwrap();
+
+        let builder = ConfigBuilder::new().root(&root);
+        let unknown = builder.build().unwrap_err().to_string();
+        let config = builder
+            .register_extendable("prefix", |root, path| Ok(Box::new(Prefix(root.join(path)))))
+            .build()
+            .unwrap();
+
+        assert!(unknown.contains("unknown extendable_type `prefix`"));
+        let extendables = config.to_extend().to_extendables();
+        assert_eq!(extendables.len(), 1);
+        assert!(extendables[0].matcher(&root.join("bazel-out/bin"), true));
+        assert!(!extendables[0].matcher(&root.join("src"), true));
+    }
+
+    #[test]
+    fn rejects_escape_keys_the_terminal_uses() {
+        let root = tempfile::tempdir().unwrap();
+        let build = |escape_key: &str| {
+            fs::write(
+                root.path().join("watchdog.toml"),
+                format!("[exec]\ncommand = \"cargo\"\n\n[watch]\nescape_key = {escape_key:?}\n"),
+            )
+            .unwrap();
+            let config = ConfigBuilder::new().root(root.path()).build();
+            config.map(|config| config.to_watch().escape_byte())
+        };
+
+        assert_eq!(build("ctrl-t").unwrap(), 0x14);
+        assert!(build("ctrl-c")
+            .unwrap_err()
+            .to_string()
+            .contains("already used by the terminal"));
+        assert!(build("alt-g")
+            .unwrap_err()
+            .to_string()
+            .contains("should look like ctrl-g"));
+    }
+
+    #[test]
+    fn layers_the_project_and_the_cli_over_the_global_config() {
+        let _env = ENV.lock().unwrap();
+        let (root, config_home) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        fs::create_dir(config_home.path().join("watchdog")).unwrap();
+        fs::write(
+            config_home.path().join("watchdog/config.toml"),
+            "[exec]\ncommand = \"make\"\n\n[exclude]\nfiles = [ { path = \".DS_Store\" } ]\n\n[watch]\ndebounce = 100\n",
+        )
+        .unwrap();
+        fs::write(
+            root.path().join("watchdog.toml"),
+            "[exec]\ncommand = \"cargo\"\n\n[exclude]\nfiles = [ { path = \"target\" } ]\n",
+        )
+        .unwrap();
+        env::set_var("XDG_CONFIG_HOME", config_home.path());
+
+        let global = build_with_args(root.path(), &[]);
+        let no_global = build_with_args(root.path(), &["--no-global-config"]);
+        let cli = build_with_args(root.path(), &["--no-clear", "--debounce", "50"]);
+        env::remove_var("XDG_CONFIG_HOME");
+
+        assert_eq!(global.to_exec().command(), "cargo");
+        assert_eq!(
+            global.to_exclude().to_exclude_files(),
+            &[".DS_Store", "target"]
+        );
+        assert_eq!(global.to_watch().debounce(), Duration::from_millis(100));
+        assert!(global.to_watch().clear());
+        assert_eq!(global.sources().get("watch.debounce"), Source::File);
+
+        assert_eq!(no_global.to_exclude().to_exclude_files(), &["target"]);
+        assert_eq!(no_global.to_watch().debounce(), Duration::from_millis(500));
+        assert_eq!(no_global.sources().get("watch.debounce"), Source::Default);
+
+        assert_eq!(cli.to_watch().debounce(), Duration::from_millis(50));
+        assert!(!cli.to_watch().clear());
+        assert_eq!(cli.sources().get("watch.debounce"), Source::Cli);
+        assert_eq!(cli.sources().get("watch.clear"), Source::Cli);
+    }
+
+    #[test]
+    fn resolves_the_global_config_paths_against_its_directory() {
+        let (root, config_home) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let config_home = fs::canonicalize(config_home.path()).unwrap();
+        let global = config_home.join("config.toml");
+        fs::write(
+            &global,
+            "[extend]\nextendables = [ { extendable_type = \"git\", path = \"ignore\" } ]\n",
+        )
+        .unwrap();
+        fs::write(
+            root.path().join("watchdog.toml"),
+            "[exec]\ncommand = \"cargo\"\n",
+        )
+        .unwrap();
+        let build = || {
+            ConfigBuilder::new()
+                .root(root.path())
+                .global_config_file(&global)
+                .build()
+        };
+
+        let missing = build().unwrap_err().to_string();
+        fs::write(config_home.join("ignore"), "*.log\n").unwrap();
+        let config = build().unwrap();
+
+        assert!(
+            missing.contains(&format!("{:?}", config_home.join("ignore"))),
+            "{missing}"
+        );
+        assert!(missing.contains(&global.display().to_string()), "{missing}");
+        let extendables = config.to_extend().to_extendables();
+        assert_eq!(extendables.len(), 1);
+        assert!(extendables[0].matcher(&config.root().join("debug.log"), false));
+    }
+
+    #[test]
+    fn selects_a_profile_on_top_of_the_config() {
+        let _env = ENV.lock().unwrap();
+        let root = tempfile::tempdir().unwrap();
+        fs::write(
+            root.path().join("watchdog.toml"),
+            "[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n\n[exclude]\nfiles = [ { path = \"target\" } ]\n\n[profile.test.exec]\nargs = [ \"--release\" ]\n\n[profile.test.exclude]\nfiles = [ { path = \"docs\" } ]\n\n[profile.docs.exec]\ncommand = \"mdbook\"\nargs = [ \"serve\" ]\n",
+        )
+        .unwrap();
+        let command_line = |config: Config| {
+            let exec = config.to_exec();
+            [exec.command()]
+                .into_iter()
+                .chain(exec.args().iter().map(String::as_str))
+                .collect::<Vec<&str>>()
+                .join(" ")
+        };
+
+        let test = build_with_args(root.path(), &["-p", "test"]);
+        assert_eq!(test.profile(), Some("test"));
+        assert_eq!(test.to_exclude().to_exclude_files(), &["target", "docs"]);
+        assert_eq!(command_line(test), "cargo run --release");
+        assert_eq!(command_line(build_with_args(root.path(), &[])), "cargo run");
+
+        // -p wins over the environment variable
+        env::set_var("WATCHDOG_PROFILE", "docs");
+        let from_env = build_with_args(root.path(), &[]);
+        let from_args = build_with_args(root.path(), &["--profile", "test"]);
+        env::remove_var("WATCHDOG_PROFILE");
+        assert_eq!(command_line(from_env), "mdbook serve");
+        assert_eq!(command_line(from_args), "cargo run --release");
+
+        let unknown = ConfigBuilder::new()
+            .root(root.path())
+            .profile("bench")
+            .build();
+        let unknown = unknown.unwrap_err().to_string();
+        assert!(
+            unknown.contains("unknown profile `bench`, expected one of `docs`, `test`"),
+            "{unknown}"
+        );
+        fs::write(
+            root.path().join("watchdog.toml"),
+            "[exec]\ncommand = \"cargo\"\n",
+        )
+        .unwrap();
+        let none = ConfigBuilder::new()
+            .root(root.path())
+            .profile("test")
+            .build();
+        assert!(none
+            .unwrap_err()
+            .to_string()
+            .contains("no [profile.<name>] tables are defined"));
+    }
+
+    #[test]
+    fn adds_the_cli_ignore_rules_to_the_config_ones_unless_replacing_them() {
+        let _env = ENV.lock().unwrap();
+        let root = tempfile::tempdir().unwrap();
+        fs::write(
+            root.path().join("watchdog.toml"),
+            "[exec]\ncommand = \"cargo\"\n\n[exclude]\nfiles = [ { path = \"target\" } ]\n\n[extend]\nextendables = [ { extendable_type = \"git\", path = \".gitignore\" } ]\n",
+        )
+        .unwrap();
+        fs::write(root.path().join(".gitignore"), "*.log\n").unwrap();
+        fs::write(root.path().join(".dockerignore"), "*.tar\n").unwrap();
+        let cli = ["-x", "dist", "-X", ".dockerignore"];
+
+        let file = build_with_args(root.path(), &[]);
+        let added = build_with_args(root.path(), &cli);
+        let replaced = build_with_args(root.path(), &[&cli[..], &["--replace-exclude"]].concat());
+        let no_config =
+            build_with_args(root.path(), &[&cli[..], &["--no-config-excludes"]].concat());
+
+        let extendables = |config: &Config| config.to_extend().to_extendables().len();
+        assert_eq!(file.to_exclude().to_exclude_files(), &["target"]);
+        assert_eq!(file.sources().get("exclude.files"), Source::File);
+        assert_eq!(added.to_exclude().to_exclude_files(), &["target", "dist"]);
+        assert_eq!(extendables(&added), 2);
+        assert_eq!(added.sources().get("exclude.files"), Source::FileAndCli);
+        assert_eq!(
+            added.sources().get("extend.extendables"),
+            Source::FileAndCli
+        );
+        for config in [replaced, no_config] {
+            assert_eq!(config.to_exclude().to_exclude_files(), &["dist"]);
+            assert_eq!(extendables(&config), 1);
+            assert_eq!(config.sources().get("exclude.files"), Source::Cli);
+            assert_eq!(config.sources().get("extend.extendables"), Source::Cli);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub struct ConfigError {
+    path: PathBuf,
+    message: String,
+    location: Option<Location>,
+    help: Option<String>,
+}
+
+#[derive(Debug)]
+struct Location {
+    line: usize,
+    column: usize,
+    source_line: String,
+    width: usize,
+}
+
+impl ConfigError {
+    pub fn new(path: &Path, message: impl Into<String>) -> Self {
+        let message = message.into();
+        let help = suggestion(&message).map(|suggestion| format!("did you mean `{suggestion}`?"));
+
+        Self {
+            path: path.to_path_buf(),
+            message,
+            location: None,
+            help,
+        }
+    }
+
+    pub fn from_toml(path: &Path, contents: &str, err: toml::de::Error) -> Self {
+        let message = err.message().trim().to_owned();
+        let help = suggestion(&message).map(|suggestion| format!("did you mean `{suggestion}`?"));
+        let location = err.span().map(|span| Location::new(contents, span));
+
+        Self {
+            path: path.to_path_buf(),
+            message,
+            location,
+            help,
+        }
+    }
+
+    pub fn from_json(path: &Path, contents: &str, err: serde_json::Error) -> Self {
+        let offset = offset(contents, err.line(), err.column());
+        Self::from_serde(path, contents, &err.to_string(), offset)
+    }
+
+    pub fn from_yaml(path: &Path, contents: &str, err: serde_norway::Error) -> Self {
+        let offset = err.location().map(|location| location.index());
+        Self::from_serde(path, contents, &err.to_string(), offset)
+    }
+
+    // serde_json and serde_norway append the position to the message instead of exposing a span
+    fn from_serde(path: &Path, contents: &str, message: &str, offset: Option<usize>) -> Self {
+        let message = match message.rfind(" at line ") {
+            Some(index) => &message[..index],
+            None => message,
+        };
+        let help = suggestion(message).map(|suggestion| format!("did you mean `{suggestion}`?"));
+        let location = offset.map(|offset| Location::new(contents, offset..offset + 1));
+
+        Self {
+            path: path.to_path_buf(),
+            message: message.to_owned(),
+            location,
+            help,
+        }
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        writeln!(f, "error: {}", self.message)?;
+
+        match &self.location {
+            Some(location) => {
+                let gutter = " ".repeat(location.line.to_string().len());
+                writeln!(
+                    f,
+                    "{gutter}--> {}:{}:{}",
+                    self.path.display(),
+                    location.line,
+                    location.column
+                )?;
+                writeln!(f, "{gutter} |")?;
+                writeln!(f, "{} | {}", location.line, location.source_line)?;
+                write!(
+                    f,
+                    "{gutter} | {}{}",
+                    " ".repeat(location.column - 1),
+                    "^".repeat(location.width)
+                )?;
+                if let Some(help) = &self.help {
+                    write!(f, "\n{gutter} = help: {help}")?;
+                }
+            }
+            None => {
+                write!(f, " --> {}", self.path.display())?;
+                if let Some(help) = &self.help {
+                    write!(f, "\n  = help: {help}")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for ConfigError {}
+
+impl Location {
+    fn new(contents: &str, span: Range<usize>) -> Self {
+        let start = span.start.min(contents.len());
+        let line_start = contents[..start].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = contents[start..]
+            .find('\n')
+            .map_or(contents.len(), |index| start + index);
+        let source_line = contents[line_start..line_end].trim_end().to_owned();
+
+        let line = contents[..start].matches('\n').count() + 1;
+        let column = contents[line_start..start].chars().count() + 1;
+        let width = contents[start..span.end.clamp(start, line_end)]
+            .chars()
+            .count()
+            .max(1);
+
+        Self {
+            line,
+            column,
+            source_line,
+            width,
+        }
+    }
+}
+
+// 1-based line and column, as reported by serde_json
+fn offset(contents: &str, line: usize, column: usize) -> Option<usize> {
+    let line_start = match line {
+        0 => return None,
+        1 => 0,
+        line => contents.match_indices('\n').nth(line - 2)?.0 + 1,
+    };
+    Some(line_start + column.saturating_sub(1))
+}
+
+// serde reports unknown fields and variants as "unknown field `exec_pr`, expected one of `exec`, ..."
+// so the typo and the candidates can be picked out of the backticks; serde_norway prefixes it with
+// the path to the value e.g "exec: unknown field ..."
+fn suggestion(message: &str) -> Option<String> {
+    let message = &message[message.find("unknown")?..];
+
+    let mut quoted = message.split('`').skip(1).step_by(2);
+    let unknown = quoted.next()?;
+
+    quoted
+        .map(|candidate| (strsim::jaro(unknown, candidate), candidate))
+        .filter(|(confidence, _)| *confidence > 0.7)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, candidate)| candidate.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{suggestion, ConfigError, Location};
+    use crate::config::FileOpt;
+    use std::path::Path;
+
+    #[test]
+    fn suggests_the_closest_candidate() {
+        let cases = [
+            (
+                "unknown field `exec_pr`, expected one of `exec`, `exec_pre`, `exclude`",
+                Some("exec_pre"),
+            ),
+            (
+                "exec: unknown field `comand`, expected one of `command`, `args`, `origin`",
+                Some("command"),
+            ),
+            (
+                "unknown variant `alway`, expected `always` or `once`",
+                Some("always"),
+            ),
+            (
+                "unknown field `verbose`, expected one of `exec`, `exec_pre`, `exclude`",
+                None,
+            ),
+            ("invalid type: string \"a\", expected a boolean", None),
+        ];
+
+        for (message, expected) in cases {
+            assert_eq!(suggestion(message).as_deref(), expected, "{message}");
+        }
+    }
+
+    #[test]
+    fn locates_the_span_in_its_line() {
+        let contents = "[exec]\ncommand = \"cargo\"\nargs = 5\n";
+        let cases = [
+            (0..6, 1, 1, "[exec]", 6),
+            (17..24, 2, 11, "command = \"cargo\"", 7),
+            (32..33, 3, 8, "args = 5", 1),
+            // spans running past the end of their line are cut at it
+            (25..40, 3, 1, "args = 5", 8),
+            (contents.len()..contents.len(), 4, 1, "", 1),
+        ];
+
+        for (span, line, column, source_line, width) in cases {
+            let location = Location::new(contents, span.clone());
+            assert_eq!(
+                (
+                    location.line,
+                    location.column,
+                    location.source_line.as_str(),
+                    location.width
+                ),
+                (line, column, source_line, width),
+                "{span:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn points_at_json_and_yaml_errors() {
+        let json = "{\n  \"exec\": {\n    \"comand\": \"cargo\"\n  }\n}\n";
+        let err = serde_json::from_str::<FileOpt>(json).unwrap_err();
+        let rendered = ConfigError::from_json(Path::new("watchdog.json"), json, err).to_string();
+        assert!(
+            rendered.starts_with("error: unknown field `comand`"),
+            "{rendered}"
+        );
+        assert!(rendered.contains("--> watchdog.json:3:"), "{rendered}");
+        assert!(
+            rendered.contains("3 |     \"comand\": \"cargo\""),
+            "{rendered}"
+        );
+        assert!(
+            rendered.contains("help: did you mean `command`?"),
+            "{rendered}"
+        );
+        assert!(!rendered.contains(" at line "), "{rendered}");
+
+        let yaml = "exec:\n  command: cargo\nwatch:\n  debounce: soon\n";
+        let err = serde_norway::from_str::<FileOpt>(yaml).unwrap_err();
+        let rendered = ConfigError::from_yaml(Path::new("watchdog.yaml"), yaml, err).to_string();
+        assert!(
+            rendered.contains("invalid type: string \"soon\""),
+            "{rendered}"
+        );
+        assert!(rendered.contains("--> watchdog.yaml:4:13"), "{rendered}");
+        assert!(rendered.contains("4 |   debounce: soon"), "{rendered}");
+    }
+}
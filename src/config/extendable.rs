@@ -0,0 +1,58 @@
+use super::{file_opt::GitignoreSerde, Extendable, ExtendableType};
+use std::{
+    collections::BTreeMap,
+    fmt::{Debug, Formatter, Result as FmtResult},
+    path::Path,
+    sync::Arc,
+};
+
+pub type BoxedExtendable = Box<dyn Extendable + Send + Sync>;
+
+// builds a matcher from the root project and the path given in [extend]
+pub type ExtendableFactory =
+    Arc<dyn Fn(&Path, &Path) -> Result<BoxedExtendable, String> + Send + Sync>;
+
+// the matchers [extend] can refer to by their `extendable_type`, git ignore files being built in
+#[derive(Clone)]
+pub struct ExtendableRegistry {
+    factories: BTreeMap<String, ExtendableFactory>,
+}
+
+impl Default for ExtendableRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            factories: BTreeMap::new(),
+        };
+        registry.register("git", |root, path| {
+            let git = GitignoreSerde::new(root, path.to_path_buf())?;
+            Ok(Box::new(ExtendableType::Git(git)))
+        });
+        registry
+    }
+}
+
+impl Debug for ExtendableRegistry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_set().entries(self.factories.keys()).finish()
+    }
+}
+
+impl ExtendableRegistry {
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        factory: impl Fn(&Path, &Path) -> Result<BoxedExtendable, String> + Send + Sync + 'static,
+    ) {
+        self.factories.insert(name.into(), Arc::new(factory));
+    }
+
+    pub fn get(&self, extendable_type: &str) -> Result<&ExtendableFactory, String> {
+        self.factories.get(extendable_type).ok_or_else(|| {
+            let names: Vec<&str> = self.factories.keys().map(String::as_str).collect();
+            format!(
+                "unknown extendable_type `{extendable_type}`, expected one of: {}",
+                names.join(", ")
+            )
+        })
+    }
+}
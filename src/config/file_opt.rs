@@ -1,50 +1,398 @@
-use super::find_root;
-use ignore::gitignore::{Gitignore, GitignoreBuilder};
-use serde::de::Visitor;
+use super::{error::ConfigError, interpolate};
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match,
+};
+use schemars::JsonSchema;
 use serde::Deserialize;
-use std::{fmt::Debug, path::Path};
-use std::{fs, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    path::Path,
+};
+use std::{env, fs, path::PathBuf};
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[schemars(title = "watchdog config")]
 pub struct FileOpt {
+    #[serde(default, alias = "include")]
+    #[schemars(description = "config files to build on, relative to this one")]
+    extends: Option<Vec<PathBuf>>,
     #[serde(default)]
+    #[schemars(description = "the command that's (re)started on every change")]
     exec: Option<FileOptExec>,
     #[serde(default)]
+    #[schemars(description = "commands that run sequentially before exec")]
     exec_pre: Option<FileOptExecPre>,
     #[serde(default)]
+    #[schemars(description = "paths containing any of these are never watched")]
     exclude: Option<FileOptExclude>,
     #[serde(default)]
+    #[schemars(description = "ignore files whose patterns aren't watched")]
     extend: Option<FileOptExtend>,
+    #[serde(default)]
+    #[schemars(description = "how file events are handled")]
+    watch: Option<FileOptWatch>,
+    #[serde(default)]
+    #[schemars(description = "named variants of the config, selected with --profile")]
+    profile: BTreeMap<String, FileOptProfile>,
+    // deprecated, left out of the schema so that editors point them out
+    #[serde(default)]
+    #[schemars(skip)]
+    run: Option<FileOptLegacyRun>,
+    #[serde(default)]
+    #[schemars(skip)]
+    build: Option<FileOptLegacyBuild>,
+    #[serde(skip)]
+    files: Vec<PathBuf>,
 }
 
 impl FileOpt {
-    pub fn parse() -> Self {
-        let root = find_root();
-        if root.is_err() {
-            return Self::default();
+    pub fn parse(root: &Path) -> Result<Self, ConfigError> {
+        let found = existing_config_files(root);
+        match found.as_slice() {
+            // the paths are kept even if the files don't exist yet, so that creating one is picked up
+            [] => Ok(Self {
+                files: CONFIG_FILE_NAMES
+                    .iter()
+                    .map(|file_name| root.join(file_name))
+                    .collect(),
+                ..Self::default()
+            }),
+            [config_path] => Self::parse_file(config_path, root),
+            [config_path, ..] => {
+                let file_names: Vec<String> = found
+                    .iter()
+                    .filter_map(|path| path.file_name())
+                    .map(|file_name| format!("{}", file_name.to_string_lossy()))
+                    .collect();
+                Err(ConfigError::new(
+                    config_path,
+                    format!(
+                        "found more than one config file in the root project: {}, keep only one of them",
+                        file_names.join(", ")
+                    ),
+                ))
+            }
         }
+    }
 
-        let root = root.unwrap();
-        let contents = fs::read_to_string(root.join("watchdog.toml")).map_err(|err| {
-            format!("An error occured while reading watchdog.toml config file: {err}")
-        });
+    // preferences shared by every project, layered under watchdog.toml; the default file is
+    // optional, unlike one that's given
+    pub fn parse_global(config_path: Option<&Path>, root: &Path) -> Result<Self, ConfigError> {
+        let config_path = match config_path {
+            Some(config_path) => fs::canonicalize(config_path).map_err(|err| {
+                ConfigError::new(
+                    config_path,
+                    format!("Could not load the global config file: {err}"),
+                )
+            })?,
+            None => match global_config_path().and_then(|path| fs::canonicalize(path).ok()) {
+                Some(config_path) => config_path,
+                None => return Ok(Self::default()),
+            },
+        };
+        // like an extended file, its paths are relative to its own directory
+        let mut file_opt = Self::parse_file(&config_path, root)?;
+        file_opt.relative_to(config_path.parent().unwrap_or(Path::new("")));
+        Ok(file_opt)
+    }
+
+    // a layer of programmatic settings, as put on top of the config files by ConfigBuilder
+    pub fn with_exec(exec: FileOptExec) -> Self {
+        Self {
+            exec: Some(exec),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_exec_pre(exec_pre: FileOptExecPre) -> Self {
+        Self {
+            exec_pre: Some(exec_pre),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_exclude(exclude: FileOptExclude) -> Self {
+        Self {
+            exclude: Some(exclude),
+            ..Self::default()
+        }
+    }
 
-        if contents.is_err() {
-            return Self::default();
+    pub fn with_extend(extend: FileOptExtend) -> Self {
+        Self {
+            extend: Some(extend),
+            ..Self::default()
         }
+    }
 
-        let file_opt: Result<FileOpt, _> = toml::from_str(&contents.unwrap()).inspect_err(|err| {
-            eprintln!(
-                "An error occurred while deserialising watchdog.toml: {}",
-                err.message()
+    // whether the lists ConfigBuilder adds to are set, keyed as in Sources
+    pub fn sets(&self, key: &str) -> bool {
+        match key {
+            "exclude.files" => self.exclude.is_some(),
+            "extend.extendables" => self.extend.is_some(),
+            _ => false,
+        }
+    }
+
+    pub fn parse_file(config_path: &Path, root: &Path) -> Result<Self, ConfigError> {
+        Self::load(config_path, root, &mut Vec::new())
+    }
+
+    // for editors to autocomplete and validate config files
+    pub fn schema() -> String {
+        let schema = schemars::schema_for!(FileOpt);
+        serde_json::to_string_pretty(&schema).expect("a JSON Schema always serialises")
+    }
+
+    // loads a config file on top of the files it extends, in the order they're listed
+    fn load(
+        config_path: &Path,
+        root: &Path,
+        extending: &mut Vec<PathBuf>,
+    ) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(config_path).map_err(|err| {
+            ConfigError::new(
+                config_path,
+                format!("An error occured while reading the config file: {err}"),
             )
+        })?;
+
+        let mut file_opt = Self::deserialize(config_path, &contents)?;
+        file_opt
+            .interpolate(root)
+            .map_err(|err| ConfigError::new(config_path, err))?;
+        file_opt.merge_legacy(config_path)?;
+        for (_, _, extend) in file_opt.tables_mut() {
+            for extendable in extend.iter_mut().flat_map(|extend| &mut extend.extendables) {
+                extendable.config_path = Some(config_path.to_path_buf());
+            }
+        }
+        file_opt.files.push(config_path.to_path_buf());
+
+        let extends = file_opt.extends.take().unwrap_or_default();
+        if extends.is_empty() {
+            return Ok(file_opt);
+        }
+
+        let dir = config_path.parent().unwrap_or(Path::new(""));
+        let mut layered = FileOpt::default();
+        extending.push(config_path.to_path_buf());
+        for extend_path in extends {
+            // canonicalised so it can be compared against the paths of file events
+            let extend_path = fs::canonicalize(dir.join(&extend_path)).map_err(|err| {
+                ConfigError::new(
+                    config_path,
+                    format!("Could not load the extended config file {extend_path:?}: {err}"),
+                )
+            })?;
+            if extending.contains(&extend_path) {
+                return Err(ConfigError::new(
+                    config_path,
+                    format!("{extend_path:?} ends up extending itself"),
+                ));
+            }
+
+            let mut extended = Self::load(&extend_path, root, extending)?;
+            extended.relative_to(extend_path.parent().unwrap_or(Path::new("")));
+            layered.layer(extended);
+        }
+        extending.pop();
+
+        layered.layer(file_opt);
+        Ok(layered)
+    }
+
+    // the format is picked by extension, anything but JSON and YAML being read as TOML
+    fn deserialize(config_path: &Path, contents: &str) -> Result<Self, ConfigError> {
+        let extension = config_path
+            .extension()
+            .and_then(|extension| extension.to_str());
+        match extension {
+            Some("json") if config_path.ends_with(PACKAGE_JSON) => {
+                serde_json::from_str::<PackageJson>(contents)
+                    .map(|package_json| package_json.watchdog.unwrap_or_default())
+                    .map_err(|err| ConfigError::from_json(config_path, contents, err))
+            }
+            Some("json") => serde_json::from_str(contents)
+                .map_err(|err| ConfigError::from_json(config_path, contents, err)),
+            Some("yaml" | "yml") => serde_norway::from_str(contents)
+                .map_err(|err| ConfigError::from_yaml(config_path, contents, err)),
+            _ => toml::from_str(contents)
+                .map_err(|err| ConfigError::from_toml(config_path, contents, err)),
+        }
+    }
+
+    // replaces `${VAR}` in the commands and paths, `${WATCHDOG_ROOT}` being the root project;
+    // profiles are left as they are until one is selected, so that the others can't fail
+    fn interpolate(&mut self, root: &Path) -> Result<(), String> {
+        for extend_path in self.extends.iter_mut().flatten() {
+            *extend_path = interpolate::interpolate(&extend_path.to_string_lossy(), root)?.into();
+        }
+        interpolate_tables(&mut self.exec, &mut self.exec_pre, &mut self.extend, root)
+    }
+
+    // the paths of an extended file are relative to its own directory rather than to the root,
+    // the ones of the files it extends in turn are already absolute and left as they are
+    fn relative_to(&mut self, dir: &Path) {
+        for (exec, exec_pre, extend) in self.tables_mut() {
+            if let Some(origin) = exec.as_mut().and_then(|exec| exec.origin.as_mut()) {
+                *origin = dir.join(&origin);
+            }
+            if let Some(origin) = exec_pre
+                .as_mut()
+                .and_then(|exec_pre| exec_pre.origin.as_mut())
+            {
+                *origin = dir.join(&origin);
+            }
+            for extendable in extend.iter_mut().flat_map(|extend| &mut extend.extendables) {
+                extendable.path = dir.join(&extendable.path).to_string_lossy().into_owned();
+            }
+        }
+    }
+
+    // the tables holding commands and paths, at the top level and in every profile
+    fn tables_mut(
+        &mut self,
+    ) -> impl Iterator<
+        Item = (
+            &mut Option<FileOptExec>,
+            &mut Option<FileOptExecPre>,
+            &mut Option<FileOptExtend>,
+        ),
+    > {
+        self.profile
+            .values_mut()
+            .map(|profile| {
+                (
+                    &mut profile.exec,
+                    &mut profile.exec_pre,
+                    &mut profile.extend,
+                )
+            })
+            .chain([(&mut self.exec, &mut self.exec_pre, &mut self.extend)])
+    }
+
+    // puts `upper` on top of `self`: scalars of `upper` override, lists are appended to unless
+    // the table of `upper` sets `replace = true`
+    pub fn layer(&mut self, upper: FileOpt) {
+        self.exec = layer_table(self.exec.take(), upper.exec, FileOptExec::layer);
+        self.exec_pre = layer_table(self.exec_pre.take(), upper.exec_pre, FileOptExecPre::layer);
+        self.exclude = layer_table(self.exclude.take(), upper.exclude, FileOptExclude::layer);
+        self.extend = layer_table(self.extend.take(), upper.extend, FileOptExtend::layer);
+        self.watch = layer_table(self.watch.take(), upper.watch, FileOptWatch::layer);
+        for (name, upper_profile) in upper.profile {
+            let profile = self.profile.remove(&name).unwrap_or_default();
+            self.profile.insert(name, profile.layer(upper_profile));
+        }
+        self.files.extend(upper.files);
+    }
+
+    // puts the selected [profile.<name>] on top of the rest of the config
+    pub fn select_profile(&mut self, name: Option<&str>, root: &Path) -> Result<(), ConfigError> {
+        let mut profiles = std::mem::take(&mut self.profile);
+        let Some(name) = name else {
+            return Ok(());
+        };
+
+        let Some(profile) = profiles.remove(name) else {
+            let config_path = self.files.last().cloned().unwrap_or_default();
+            let message = if profiles.is_empty() {
+                format!("unknown profile `{name}`, no [profile.<name>] tables are defined")
+            } else {
+                let names: Vec<String> = profiles.keys().map(|name| format!("`{name}`")).collect();
+                format!(
+                    "unknown profile `{name}`, expected one of {}",
+                    names.join(", ")
+                )
+            };
+            return Err(ConfigError::new(&config_path, message));
+        };
+
+        let mut profile = profile;
+        interpolate_tables(
+            &mut profile.exec,
+            &mut profile.exec_pre,
+            &mut profile.extend,
+            root,
+        )
+        .map_err(|err| {
+            let config_path = self.files.last().cloned().unwrap_or_default();
+            ConfigError::new(&config_path, err)
+        })?;
+        self.layer(FileOpt {
+            exec: profile.exec,
+            exec_pre: profile.exec_pre,
+            exclude: profile.exclude,
+            extend: profile.extend,
+            watch: profile.watch,
+            ..FileOpt::default()
         });
+        Ok(())
+    }
 
-        if file_opt.is_err() {
-            return Self::default();
+    // maps the [run]/[build] tables that older versions documented onto [exec]/[exec_pre]
+    fn merge_legacy(&mut self, config_path: &Path) -> Result<(), ConfigError> {
+        let (run, build) = (self.run.take(), self.build.take());
+        if run.is_none() && build.is_none() {
+            return Ok(());
+        }
+
+        eprintln!(
+            "warning: [run] and [build] in {config_path:?} are deprecated, \
+            use `watchdog migrate` to rewrite them as [exec] and [exec_pre]"
+        );
+
+        let precompile = run
+            .as_ref()
+            .and_then(|run| run.precompile)
+            .unwrap_or_default();
+
+        if let Some(run) = run {
+            match self.exec {
+                Some(_) => eprintln!("warning: [run] is ignored since [exec] is set as well"),
+                None => {
+                    self.exec = Some(FileOptExec {
+                        command: run.command,
+                        args: run.args,
+                        origin: run.origin,
+                        interactive: None,
+                        replace: false,
+                    })
+                }
+            }
+        }
+
+        match (precompile, build) {
+            (true, None) => {
+                return Err(ConfigError::new(
+                    config_path,
+                    "`precompile = true` under [run] requires a [build] table",
+                ))
+            }
+            (true, Some(_)) if self.exec_pre.is_some() => {
+                eprintln!("warning: [build] is ignored since [exec_pre] is set as well")
+            }
+            (true, Some(build)) => {
+                self.exec_pre = Some(FileOptExecPre {
+                    origin: build.origin,
+                    when: Some(FileOptWhen::Always),
+                    commands: Some(vec![FileOptExecPreCommand {
+                        command: build.command,
+                        args: build.args,
+                    }]),
+                    replace: false,
+                })
+            }
+            (false, Some(_)) => eprintln!(
+                "warning: [build] is ignored since `precompile = true` isn't set under [run]"
+            ),
+            (false, None) => {}
         }
 
-        file_opt.unwrap()
+        Ok(())
     }
 
     pub fn take_exec(&mut self) -> Option<FileOptExec> {
@@ -62,16 +410,112 @@ impl FileOpt {
     pub fn take_extend(&mut self) -> Option<FileOptExtend> {
         self.extend.take()
     }
+
+    pub fn take_watch(&mut self) -> Option<FileOptWatch> {
+        self.watch.take()
+    }
+
+    pub fn take_files(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.files)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct FileOptProfile {
+    #[serde(default)]
+    exec: Option<FileOptExec>,
+    #[serde(default)]
+    exec_pre: Option<FileOptExecPre>,
+    #[serde(default)]
+    exclude: Option<FileOptExclude>,
+    #[serde(default)]
+    extend: Option<FileOptExtend>,
+    #[serde(default)]
+    watch: Option<FileOptWatch>,
+}
+
+impl FileOptProfile {
+    fn layer(self, upper: Self) -> Self {
+        Self {
+            exec: layer_table(self.exec, upper.exec, FileOptExec::layer),
+            exec_pre: layer_table(self.exec_pre, upper.exec_pre, FileOptExecPre::layer),
+            exclude: layer_table(self.exclude, upper.exclude, FileOptExclude::layer),
+            extend: layer_table(self.extend, upper.extend, FileOptExtend::layer),
+            watch: layer_table(self.watch, upper.watch, FileOptWatch::layer),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileOptLegacyRun {
+    command: Option<String>,
+    args: Option<Vec<String>>,
+    #[serde(default)]
+    precompile: Option<bool>,
+    origin: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileOptLegacyBuild {
+    command: Option<String>,
+    args: Option<Vec<String>>,
+    origin: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct FileOptExec {
+    #[serde(default)]
     command: Option<String>,
+    #[serde(default)]
     args: Option<Vec<String>>,
+    #[serde(default)]
+    #[schemars(
+        description = "where to run the command from, relative to the root project or to the extended file setting it"
+    )]
     origin: Option<PathBuf>,
+    #[serde(default)]
+    #[schemars(
+        description = "pass what's typed in the terminal to the command, watchdog's keys then follow the escape key"
+    )]
+    interactive: Option<bool>,
+    #[serde(default)]
+    #[schemars(
+        description = "replace the lists of the extended config files instead of adding to them"
+    )]
+    replace: bool,
 }
 
 impl FileOptExec {
+    pub fn new(command: String, args: Vec<String>) -> Self {
+        Self {
+            command: Some(command),
+            args: Some(args),
+            origin: None,
+            interactive: None,
+            replace: true,
+        }
+    }
+
+    fn layer(mut self, upper: Self) -> Self {
+        // the args go with their command, so setting another command replaces them as well
+        self.args = match upper.command {
+            Some(_) => upper.args,
+            None => layer_list(self.args, upper.args, upper.replace),
+        };
+        self.command = upper.command.or(self.command);
+        self.origin = upper.origin.or(self.origin);
+        self.interactive = upper.interactive.or(self.interactive);
+        self
+    }
+
+    pub fn take_interactive(&mut self) -> Option<bool> {
+        self.interactive.take()
+    }
+
     pub fn take_command(&mut self) -> Option<String> {
         self.command.take()
     }
@@ -85,16 +529,44 @@ impl FileOptExec {
     }
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct FileOptExecPre {
     #[serde(default)]
+    #[schemars(
+        description = "where to run the commands from, relative to the root project or to the extended file setting it"
+    )]
     origin: Option<PathBuf>,
     #[serde(default)]
+    #[schemars(
+        description = "`always` runs the commands before every run, `once` only on startup"
+    )]
     when: Option<FileOptWhen>,
     commands: Option<Vec<FileOptExecPreCommand>>,
+    #[serde(default)]
+    #[schemars(
+        description = "replace the lists of the extended config files instead of adding to them"
+    )]
+    replace: bool,
 }
 
 impl FileOptExecPre {
+    pub fn new(when: FileOptWhen, commands: Vec<FileOptExecPreCommand>) -> Self {
+        Self {
+            origin: None,
+            when: Some(when),
+            commands: Some(commands),
+            replace: true,
+        }
+    }
+
+    fn layer(mut self, upper: Self) -> Self {
+        self.origin = upper.origin.or(self.origin);
+        self.when = upper.when.or(self.when);
+        self.commands = layer_list(self.commands, upper.commands, upper.replace);
+        self
+    }
+
     pub fn take_when(&mut self) -> Option<FileOptWhen> {
         self.when.take()
     }
@@ -108,7 +580,7 @@ impl FileOptExecPre {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub enum FileOptWhen {
     #[serde(rename = "once")]
     Once,
@@ -116,13 +588,23 @@ pub enum FileOptWhen {
     Always,
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct FileOptExecPreCommand {
+    #[serde(default)]
     command: Option<String>,
+    #[serde(default)]
     args: Option<Vec<String>>,
 }
 
 impl FileOptExecPreCommand {
+    pub fn new(command: String, args: Vec<String>) -> Self {
+        Self {
+            command: Some(command),
+            args: Some(args),
+        }
+    }
+
     pub fn take_command(&mut self) -> Option<String> {
         self.command.take()
     }
@@ -132,12 +614,141 @@ impl FileOptExecPreCommand {
     }
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct FileOptWatch {
+    #[serde(default)]
+    #[schemars(description = "don't restart on saves that leave the contents of a file unchanged")]
+    hash_contents: Option<bool>,
+    #[serde(default)]
+    #[schemars(description = "the kind of file events that trigger a run")]
+    events: Option<Vec<FileOptEventKind>>,
+    #[serde(default)]
+    #[schemars(description = "re-execute watchdog itself whenever exec_pre rebuilds its binary")]
+    reexec: Option<bool>,
+    #[serde(default)]
+    #[schemars(description = "clear the screen before every run, defaults to true")]
+    clear: Option<bool>,
+    #[serde(default)]
+    #[schemars(description = "milliseconds to wait after a run is triggered, defaults to 500")]
+    debounce: Option<u64>,
+    #[serde(default)]
+    #[schemars(
+        description = "read keys from the terminal, e.g r to restart or q to quit, defaults to true"
+    )]
+    keys: Option<bool>,
+    #[serde(default)]
+    #[schemars(
+        description = "the key preceding watchdog's keys when exec is interactive, defaults to ctrl-g"
+    )]
+    escape_key: Option<String>,
+    #[serde(default)]
+    #[schemars(
+        description = "run once on resume if anything changed while paused, defaults to false"
+    )]
+    run_on_resume: Option<bool>,
+    #[serde(default)]
+    #[schemars(
+        description = "replace the lists of the extended config files instead of adding to them"
+    )]
+    replace: bool,
+}
+
+impl FileOptWatch {
+    fn layer(mut self, upper: Self) -> Self {
+        self.hash_contents = upper.hash_contents.or(self.hash_contents);
+        self.events = layer_list(self.events, upper.events, upper.replace);
+        self.reexec = upper.reexec.or(self.reexec);
+        self.clear = upper.clear.or(self.clear);
+        self.debounce = upper.debounce.or(self.debounce);
+        self.keys = upper.keys.or(self.keys);
+        self.escape_key = upper.escape_key.or(self.escape_key);
+        self.run_on_resume = upper.run_on_resume.or(self.run_on_resume);
+        self
+    }
+
+    pub fn take_hash_contents(&mut self) -> Option<bool> {
+        self.hash_contents.take()
+    }
+
+    pub fn take_events(&mut self) -> Option<Vec<FileOptEventKind>> {
+        self.events.take()
+    }
+
+    pub fn take_reexec(&mut self) -> Option<bool> {
+        self.reexec.take()
+    }
+
+    pub fn take_clear(&mut self) -> Option<bool> {
+        self.clear.take()
+    }
+
+    pub fn take_debounce(&mut self) -> Option<u64> {
+        self.debounce.take()
+    }
+
+    pub fn take_keys(&mut self) -> Option<bool> {
+        self.keys.take()
+    }
+
+    pub fn take_escape_key(&mut self) -> Option<String> {
+        self.escape_key.take()
+    }
+
+    pub fn take_run_on_resume(&mut self) -> Option<bool> {
+        self.run_on_resume.take()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub enum FileOptEventKind {
+    #[serde(rename = "create")]
+    Create,
+    #[serde(rename = "remove")]
+    Remove,
+    #[serde(rename = "rename")]
+    Rename,
+    #[serde(rename = "modify")]
+    Modify,
+    #[serde(rename = "modify:data")]
+    ModifyData,
+    #[serde(rename = "modify:metadata")]
+    ModifyMetadata,
+    #[serde(rename = "access")]
+    Access,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct FileOptExclude {
+    #[serde(default)]
     files: Vec<IgnorablePath>,
+    #[serde(default)]
+    #[schemars(
+        description = "replace the lists of the extended config files instead of adding to them"
+    )]
+    replace: bool,
 }
 
 impl FileOptExclude {
+    pub fn new(files: Vec<String>) -> Self {
+        Self {
+            files: files
+                .into_iter()
+                .map(|path| IgnorablePath { path })
+                .collect(),
+            replace: false,
+        }
+    }
+
+    fn layer(mut self, upper: Self) -> Self {
+        if upper.replace {
+            self.files.clear();
+        }
+        self.files.extend(upper.files);
+        self
+    }
+
     pub fn take_exclude_files(self) -> Vec<String> {
         self.files
             .into_iter()
@@ -146,7 +757,8 @@ impl FileOptExclude {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 struct IgnorablePath {
     path: String,
 }
@@ -157,74 +769,506 @@ impl IgnorablePath {
     }
 }
 
-pub trait Extendable: Debug {
+// displayed as listed by `watchdog check` and when watchdog starts
+pub trait Extendable: Debug + Display {
     fn matcher(&self, path: &Path, is_dir: bool) -> bool;
+
+    // why the path matched, as shown by `watchdog check`
+    fn explain(&self, path: &Path, is_dir: bool) -> Option<String> {
+        self.matcher(path, is_dir).then(|| format!("{self}"))
+    }
+
+    // what's wrong with it, e.g an external tool that isn't installed, reported by `watchdog check`
+    fn problem(&self, _root: &Path) -> Option<String> {
+        None
+    }
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(tag = "extendable_type", content = "path")]
+#[derive(Debug)]
+#[non_exhaustive]
 pub enum ExtendableType {
-    #[serde(rename = "git")]
     Git(GitignoreSerde),
 }
 
 impl Extendable for ExtendableType {
     fn matcher(&self, path: &Path, is_dir: bool) -> bool {
         match self {
-            ExtendableType::Git(wrapper) => wrapper
-                .0
-                .matched_path_or_any_parents(path, is_dir)
-                .is_ignore(),
+            ExtendableType::Git(gitignore) => gitignore.matched(path, is_dir),
+        }
+    }
+
+    fn explain(&self, path: &Path, is_dir: bool) -> Option<String> {
+        match self {
+            ExtendableType::Git(gitignore) => gitignore.explain(path, is_dir),
         }
     }
 }
 
-#[derive(Debug, Default, Deserialize)]
+impl Display for ExtendableType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            ExtendableType::Git(gitignore) => write!(f, "git {:?}", gitignore.path),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct FileOptExtend {
-    extendables: Vec<ExtendableType>,
+    #[serde(default)]
+    extendables: Vec<FileOptExtendable>,
+    #[serde(default)]
+    #[schemars(
+        description = "replace the lists of the extended config files instead of adding to them"
+    )]
+    replace: bool,
 }
 
 impl FileOptExtend {
-    pub fn take_extendables(self) -> Vec<ExtendableType> {
+    pub fn new(extendables: Vec<FileOptExtendable>) -> Self {
+        Self {
+            extendables,
+            replace: false,
+        }
+    }
+
+    fn layer(mut self, upper: Self) -> Self {
+        if upper.replace {
+            self.extendables.clear();
+        }
+        self.extendables.extend(upper.extendables);
+        self
+    }
+
+    pub fn take_extendables(self) -> Vec<FileOptExtendable> {
         self.extendables
     }
 }
 
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct FileOptExtendable {
+    #[schemars(
+        description = "`git` for a git ignore file, or the name of a matcher registered by the program embedding watchdog"
+    )]
+    extendable_type: String,
+    #[schemars(description = "relative to the root project or to the extended file setting it")]
+    path: String,
+    // the config file listing it, to report the errors of loading it against
+    #[serde(skip)]
+    #[schemars(skip)]
+    config_path: Option<PathBuf>,
+}
+
+impl FileOptExtendable {
+    pub fn new(extendable_type: String, path: String) -> Self {
+        Self {
+            extendable_type,
+            path,
+            config_path: None,
+        }
+    }
+
+    pub fn extendable_type(&self) -> &str {
+        &self.extendable_type
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn config_path(&self) -> Option<&Path> {
+        self.config_path.as_deref()
+    }
+}
+
+// looked up in the root project, along with the `watchdog` key of package.json
+const CONFIG_FILE_NAMES: [&str; 4] = [
+    "watchdog.toml",
+    "watchdog.yaml",
+    "watchdog.yml",
+    "watchdog.json",
+];
+
+pub(super) const PACKAGE_JSON: &str = "package.json";
+
+#[derive(Deserialize)]
+struct PackageJson {
+    watchdog: Option<FileOpt>,
+}
+
+pub fn existing_config_files(root: &Path) -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = CONFIG_FILE_NAMES
+        .iter()
+        .map(|file_name| root.join(file_name))
+        .filter(|config_path| config_path.exists())
+        .collect();
+    let package_json = root.join(PACKAGE_JSON);
+    if has_package_json_config(&package_json) {
+        found.push(package_json);
+    }
+    found
+}
+
+// package.json only counts as a config file with a `watchdog` key, otherwise its changes trigger
+// a run like those of any other file
+pub(super) fn has_package_json_config(package_json: &Path) -> bool {
+    fs::read_to_string(package_json)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<serde_json::Value>(&contents).ok())
+        .is_some_and(|value| value.get("watchdog").is_some())
+}
+
+fn global_config_path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|config_home| !config_home.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .map(|config_home| config_home.join("watchdog").join("config.toml"))
+}
+
+fn interpolate_tables(
+    exec: &mut Option<FileOptExec>,
+    exec_pre: &mut Option<FileOptExecPre>,
+    extend: &mut Option<FileOptExtend>,
+    root: &Path,
+) -> Result<(), String> {
+    let string = |value: &mut String| {
+        *value = interpolate::interpolate(value, root)?;
+        Ok::<_, String>(())
+    };
+    let path = |value: &mut PathBuf| {
+        *value = interpolate::interpolate(&value.to_string_lossy(), root)?.into();
+        Ok::<_, String>(())
+    };
+
+    if let Some(exec) = exec {
+        exec.command.iter_mut().try_for_each(string)?;
+        exec.args.iter_mut().flatten().try_for_each(string)?;
+        exec.origin.iter_mut().try_for_each(path)?;
+    }
+    if let Some(exec_pre) = exec_pre {
+        exec_pre.origin.iter_mut().try_for_each(path)?;
+        for command in exec_pre.commands.iter_mut().flatten() {
+            command.command.iter_mut().try_for_each(string)?;
+            command.args.iter_mut().flatten().try_for_each(string)?;
+        }
+    }
+    for extendable in extend.iter_mut().flat_map(|extend| &mut extend.extendables) {
+        string(&mut extendable.path)?;
+    }
+    Ok(())
+}
+
+fn layer_table<T>(lower: Option<T>, upper: Option<T>, layer: fn(T, T) -> T) -> Option<T> {
+    match (lower, upper) {
+        (Some(lower), Some(upper)) => Some(layer(lower, upper)),
+        (lower, upper) => upper.or(lower),
+    }
+}
+
+fn layer_list<T>(lower: Option<Vec<T>>, upper: Option<Vec<T>>, replace: bool) -> Option<Vec<T>> {
+    match (lower, upper) {
+        (Some(mut lower), Some(upper)) if !replace => {
+            lower.extend(upper);
+            Some(lower)
+        }
+        (lower, upper) => upper.or(lower),
+    }
+}
+
+// the patterns of a git ignore file, the ignore crate staying out of the public API
 #[derive(Debug)]
-pub struct GitignoreSerde(pub Gitignore);
+pub struct GitignoreSerde {
+    gitignore: Gitignore,
+    path: PathBuf,
+}
 
-impl<'de> serde::Deserialize<'de> for GitignoreSerde {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        struct GitignorePathVisitor;
+impl GitignoreSerde {
+    pub(crate) fn new(root: &Path, path: PathBuf) -> Result<Self, String> {
+        let mut builder = GitignoreBuilder::new(root);
+        if let Some(err) = builder.add(root.join(&path)) {
+            return Err(err.to_string());
+        }
+        let git = builder
+            .build()
+            .map_err(|err| format!("Failed to build Gitignore: {err}"))?;
 
-        impl Visitor<'_> for GitignorePathVisitor {
-            type Value = GitignoreSerde;
+        Ok(GitignoreSerde {
+            gitignore: git,
+            path,
+        })
+    }
 
-            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                formatter.write_str("a path string to a .gitignore file")
-            }
+    // the file as listed in the config
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
 
-            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
-            where
-                E: serde::de::Error,
-            {
-                let root = find_root().unwrap_or_default();
-                let mut builder = GitignoreBuilder::new(&root);
-                let err = builder.add(root.join(v));
-                if err.is_some() {
-                    eprintln!("Something went wrong with adding '{v}' to the path {root:?}. GitignoreBuilder will now be empty.\nError: {err:?}")
-                }
-                let git = builder
-                    .build()
-                    .map_err(|err| E::custom(format!("Failed to build Gitignore: {err}")))?;
+    // true if the path, or one of its parents, is ignored
+    pub fn matched(&self, path: &Path, is_dir: bool) -> bool {
+        self.gitignore
+            .matched_path_or_any_parents(path, is_dir)
+            .is_ignore()
+    }
 
-                Ok(GitignoreSerde(git))
-            }
+    fn explain(&self, path: &Path, is_dir: bool) -> Option<String> {
+        match self.gitignore.matched_path_or_any_parents(path, is_dir) {
+            Match::Ignore(glob) => Some(format!(
+                "pattern `{}` in git ignore file {:?}",
+                glob.original(),
+                self.path
+            )),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{layer_list, layer_table, FileOpt, GitignoreSerde};
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    fn file_opt(contents: &str) -> FileOpt {
+        toml::from_str(contents).unwrap()
+    }
+
+    fn paths(file_opt: &FileOpt) -> (Option<PathBuf>, Option<PathBuf>, Vec<String>) {
+        let exec = file_opt.exec.as_ref().and_then(|exec| exec.origin.clone());
+        let exec_pre = file_opt.exec_pre.as_ref();
+        let extend = file_opt
+            .extend
+            .iter()
+            .flat_map(|extend| &extend.extendables);
+        (
+            exec,
+            exec_pre.and_then(|exec_pre| exec_pre.origin.clone()),
+            extend.map(|extendable| extendable.path.clone()).collect(),
+        )
+    }
+
+    #[test]
+    fn layers_scalars_over_and_lists_after_the_lower_ones() {
+        assert_eq!(layer_table(Some(1), Some(2), |_, upper| upper), Some(2));
+        assert_eq!(layer_table(Some(1), None, |_, upper| upper), Some(1));
+        assert_eq!(layer_table(None, Some(2), |_, upper| upper), Some(2));
+        assert_eq!(
+            layer_list(Some(vec![1]), Some(vec![2]), false),
+            Some(vec![1, 2])
+        );
+        assert_eq!(
+            layer_list(Some(vec![1]), Some(vec![2]), true),
+            Some(vec![2])
+        );
+        assert_eq!(layer_list(Some(vec![1]), None, true), Some(vec![1]));
+
+        let mut layered = file_opt(
+            "[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n\n[exclude]\nfiles = [ { path = \"target\" } ]\n\n[watch]\nevents = [ \"create\" ]\ndebounce = 100\n",
+        );
+        layered.layer(file_opt(
+            "[exec]\nargs = [ \"--release\" ]\n\n[exclude]\nreplace = true\nfiles = [ { path = \"dist\" } ]\n\n[watch]\nevents = [ \"remove\" ]\n",
+        ));
+
+        let exec = layered.exec.as_ref().unwrap();
+        assert_eq!(exec.command.as_deref(), Some("cargo"));
+        assert_eq!(
+            exec.args.as_deref(),
+            Some(&["run".into(), "--release".into()][..])
+        );
+        assert_eq!(
+            layered.exclude.unwrap().take_exclude_files(),
+            ["dist".to_owned()]
+        );
+        let watch = layered.watch.unwrap();
+        assert_eq!(watch.events.map(|events| events.len()), Some(2));
+        assert_eq!(watch.debounce, Some(100));
+    }
+
+    #[test]
+    fn another_command_replaces_the_args() {
+        let lower = "[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n";
+        let cases = [
+            (
+                "[exec]\ncommand = \"npm\"\nargs = [ \"start\" ]\n",
+                "npm",
+                vec!["start"],
+            ),
+            ("[exec]\ncommand = \"make\"\n", "make", vec![]),
+            (
+                "[exec]\nargs = [ \"--release\" ]\n",
+                "cargo",
+                vec!["run", "--release"],
+            ),
+        ];
+
+        for (upper, command, args) in cases {
+            let mut layered = file_opt(lower);
+            layered.layer(file_opt(upper));
+            let mut exec = layered.exec.unwrap();
+            assert_eq!(exec.take_command().as_deref(), Some(command), "{upper}");
+            assert_eq!(exec.take_args().unwrap_or_default(), args, "{upper}");
+        }
+    }
+
+    #[test]
+    fn rejects_files_extending_themselves() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(root.join("watchdog.toml"), "extends = [ \"a.toml\" ]\n").unwrap();
+        fs::write(root.join("a.toml"), "extends = [ \"b.toml\" ]\n").unwrap();
+        fs::write(root.join("b.toml"), "extends = [ \"a.toml\" ]\n").unwrap();
+
+        let err = FileOpt::parse(&root).unwrap_err().to_string();
+
+        assert!(err.contains("a.toml\" ends up extending itself"), "{err}");
+        assert!(err.contains("b.toml"), "{err}");
+    }
+
+    #[test]
+    fn resolves_the_paths_of_extended_files_against_their_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = fs::canonicalize(dir.path()).unwrap();
+        let (root, shared) = (dir.join("project"), dir.join("shared"));
+        fs::create_dir_all(shared.join("nested")).unwrap();
+        fs::create_dir(&root).unwrap();
+        fs::write(
+            root.join("watchdog.toml"),
+            "extends = [ \"../shared/base.toml\" ]\n\n[exec_pre]\norigin = \"web\"\n",
+        )
+        .unwrap();
+        fs::write(
+            shared.join("base.toml"),
+            "extends = [ \"nested/tools.toml\" ]\n\n[exec]\norigin = \"app\"\n\n[profile.test.exec]\norigin = \"tests\"\n",
+        )
+        .unwrap();
+        fs::write(
+            shared.join("nested/tools.toml"),
+            "[exec_pre]\norigin = \"tools\"\n\n[extend]\nextendables = [ { extendable_type = \"git\", path = \".gitignore\" } ]\n",
+        )
+        .unwrap();
+
+        let mut file_opt = FileOpt::parse(&root).unwrap();
+
+        // the root file's own origin stays relative to the root
+        let gitignore = shared
+            .join("nested/.gitignore")
+            .to_string_lossy()
+            .into_owned();
+        assert_eq!(
+            paths(&file_opt),
+            (
+                Some(shared.join("app")),
+                Some(PathBuf::from("web")),
+                vec![gitignore]
+            )
+        );
+        file_opt.select_profile(Some("test"), &root).unwrap();
+        assert_eq!(paths(&file_opt).0, Some(shared.join("tests")));
+    }
+
+    #[test]
+    fn interpolates_the_root_it_is_given() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("watchdog.toml");
+        fs::write(
+            &config_path,
+            "[exec]\ncommand = \"${WATCHDOG_ROOT}/run.sh\"\nargs = [ \"--mode=${WATCHDOG_TEST_UNSET:-dev}\" ]\n\n[profile.test.exec_pre]\norigin = \"${WATCHDOG_ROOT}/tests\"\ncommands = [ { command = \"make\" } ]\n",
+        )
+        .unwrap();
+
+        for root in ["/one", "/two"] {
+            let mut file_opt = FileOpt::parse_file(&config_path, root.as_ref()).unwrap();
+            file_opt
+                .select_profile(Some("test"), root.as_ref())
+                .unwrap();
+            let exec = file_opt.exec.as_ref().unwrap();
+            assert_eq!(exec.command, Some(format!("{root}/run.sh")));
+            assert_eq!(exec.args.as_deref(), Some(&["--mode=dev".to_owned()][..]));
+            assert_eq!(paths(&file_opt).1, Some(PathBuf::from(root).join("tests")));
         }
 
-        deserializer.deserialize_str(GitignorePathVisitor)
+        fs::write(
+            &config_path,
+            "[exec]\ncommand = \"${WATCHDOG_TEST_UNSET}\"\n",
+        )
+        .unwrap();
+        let err = FileOpt::parse_file(&config_path, dir.path())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("`WATCHDOG_TEST_UNSET` is not set"), "{err}");
+        assert!(err.contains("watchdog.toml"), "{err}");
+
+        // only the selected profile has to interpolate
+        fs::write(
+            &config_path,
+            "[exec]\ncommand = \"mdbook\"\n\n[profile.docs.exec]\nargs = [ \"--port\", \"${WATCHDOG_TEST_UNSET}\" ]\n",
+        )
+        .unwrap();
+        let mut file_opt = FileOpt::parse_file(&config_path, dir.path()).unwrap();
+        file_opt.clone().select_profile(None, dir.path()).unwrap();
+        let err = file_opt
+            .select_profile(Some("docs"), dir.path())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("`WATCHDOG_TEST_UNSET` is not set"), "{err}");
+    }
+
+    #[test]
+    fn rejects_more_than_one_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(root.join("watchdog.toml"), "[exec]\ncommand = \"cargo\"\n").unwrap();
+        fs::write(root.join("watchdog.yaml"), "exec:\n  command: cargo\n").unwrap();
+
+        let err = FileOpt::parse(&root).unwrap_err().to_string();
+
+        assert!(
+            err.contains("found more than one config file in the root project: watchdog.toml, watchdog.yaml, keep only one of them"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn matches_the_patterns_of_a_git_ignore_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n/target\n").unwrap();
+
+        let gitignore = GitignoreSerde::new(&root, ".gitignore".into()).unwrap();
+
+        assert_eq!(gitignore.path(), Path::new(".gitignore"));
+        assert!(gitignore.matched(&root.join("src/debug.log"), false));
+        assert!(gitignore.matched(&root.join("target/debug/app"), false));
+        assert!(!gitignore.matched(&root.join("src/target.rs"), false));
+        assert!(GitignoreSerde::new(&root, "missing".into()).is_err());
+    }
+
+    #[test]
+    fn picks_the_format_by_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        let cases = [
+            ("watchdog.toml", "[exec]\ncommand = \"cargo\"\n"),
+            ("watchdog.yaml", "exec:\n  command: cargo\n"),
+            ("watchdog.yml", "exec:\n  command: cargo\n"),
+            ("watchdog.json", r#"{ "exec": { "command": "cargo" } }"#),
+            (
+                "package.json",
+                r#"{ "name": "app", "watchdog": { "exec": { "command": "cargo" } } }"#,
+            ),
+            // anything else is read as TOML
+            ("base.conf", "[exec]\ncommand = \"cargo\"\n"),
+        ];
+
+        for (file_name, contents) in cases {
+            let config_path = root.join(file_name);
+            fs::write(&config_path, contents).unwrap();
+            let file_opt = FileOpt::parse_file(&config_path, &root).unwrap();
+            let command = file_opt.exec.and_then(|exec| exec.command);
+            assert_eq!(command.as_deref(), Some("cargo"), "{file_name}");
+        }
     }
 }
@@ -0,0 +1,112 @@
+use std::{env, path::Path};
+
+// replaces `${VAR}` and `${VAR:-default}` with the value of the environment variable, the default
+// being used when the variable is unset or empty; `$${` is kept as a literal `${`
+pub fn interpolate(value: &str, root: &Path) -> Result<String, String> {
+    interpolate_with(value, |name| match name {
+        // the root watchdog is about to watch, not the one of a watchdog that might have started it
+        "WATCHDOG_ROOT" => Some(root.to_string_lossy().into_owned()),
+        name => env::var(name).ok(),
+    })
+}
+
+fn interpolate_with(
+    value: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<String, String> {
+    let mut interpolated = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find('$') {
+        interpolated.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(escaped) = rest.strip_prefix("$${") {
+            interpolated.push_str("${");
+            rest = escaped;
+            continue;
+        }
+        let Some(variable) = rest.strip_prefix("${") else {
+            interpolated.push('$');
+            rest = &rest[1..];
+            continue;
+        };
+
+        let end = variable
+            .find('}')
+            .ok_or_else(|| format!("unterminated `${{` in {value:?}"))?;
+        let (name, default) = match variable[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&variable[..end], None),
+        };
+        if !is_variable_name(name) {
+            return Err(format!(
+                "invalid environment variable name {name:?} in {value:?}"
+            ));
+        }
+
+        match (lookup(name), default) {
+            (Some(value), Some(default)) if value.is_empty() => interpolated.push_str(default),
+            (Some(value), _) => interpolated.push_str(&value),
+            (None, Some(default)) => interpolated.push_str(default),
+            (None, None) => {
+                return Err(format!(
+                    "environment variable `{name}` is not set, set it or give a default with `${{{name}:-default}}`"
+                ))
+            }
+        }
+        rest = &variable[end + 1..];
+    }
+    interpolated.push_str(rest);
+
+    Ok(interpolated)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|char| char.is_ascii_alphabetic() || char == '_')
+        && chars.all(|char| char.is_ascii_alphanumeric() || char == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::interpolate_with;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "PORT" => Some("8080".into()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn replaces_variables_and_defaults() {
+        assert_eq!(
+            interpolate_with("--port=${PORT} --features=${FEATURES:-dev,tracing}", lookup),
+            Ok("--port=8080 --features=dev,tracing".into())
+        );
+        assert_eq!(
+            interpolate_with("${EMPTY:-fallback}", lookup),
+            Ok("fallback".into())
+        );
+        assert_eq!(interpolate_with("[${EMPTY}]", lookup), Ok("[]".into()));
+    }
+
+    #[test]
+    fn keeps_plain_and_escaped_dollars() {
+        assert_eq!(
+            interpolate_with("echo $HOME $$ $${PORT}", lookup),
+            Ok("echo $HOME $$ ${PORT}".into())
+        );
+    }
+
+    #[test]
+    fn rejects_undefined_and_malformed_variables() {
+        assert!(interpolate_with("${UNDEFINED}", lookup).is_err());
+        assert!(interpolate_with("${PORT", lookup).is_err());
+        assert!(interpolate_with("${1PORT}", lookup).is_err());
+    }
+}
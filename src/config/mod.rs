@@ -1,16 +1,31 @@
 mod args_opt;
+mod builder;
+mod error;
+mod extendable;
 mod file_opt;
+mod interpolate;
 
+pub(crate) use args_opt::{ArgsOpt, ArgsOptCheck, ArgsOptCommand, ArgsOptInit};
 use args_opt::{
-    ArgsOpt, ArgsOptExec, ArgsOptExecPre, ArgsOptExtend, ArgsOptExtendableType, ArgsOptWhen,
+    ArgsOptEventKind, ArgsOptExec, ArgsOptExecPre, ArgsOptExtend, ArgsOptExtendableType,
+    ArgsOptWatch, ArgsOptWhen,
 };
-use clap::Parser;
-use file_opt::GitignoreSerde;
+pub use builder::ConfigBuilder;
+use error::ConfigError;
+pub use extendable::BoxedExtendable;
+use extendable::ExtendableRegistry;
 pub use file_opt::{
-    Extendable, ExtendableType, FileOpt, FileOptExclude, FileOptExec, FileOptExecPre,
-    FileOptExtend, FileOptWhen,
+    existing_config_files, Extendable, ExtendableType, FileOpt, FileOptEventKind, FileOptExclude,
+    FileOptExec, FileOptExecPre, FileOptExecPreCommand, FileOptExtend, FileOptExtendable,
+    FileOptWatch, FileOptWhen, GitignoreSerde,
+};
+use file_opt::{has_package_json_config, PACKAGE_JSON};
+use notify::{event::ModifyKind, EventKind};
+use std::{
+    collections::BTreeMap,
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    path::Path,
 };
-use ignore::gitignore::GitignoreBuilder;
 use std::{
     env::current_dir,
     error::Error,
@@ -19,8 +34,8 @@ use std::{
     path::PathBuf,
     process::Command,
     str::FromStr,
+    time::Duration,
 };
-use std::{fmt::Debug, path::Path};
 
 #[derive(Debug, Default)]
 pub struct Config {
@@ -28,42 +43,93 @@ pub struct Config {
     exec_pre: Option<ExecPre>,
     exclude: Exclude,
     extend: Extend,
+    watch: Watch,
     root: PathBuf,
+    config_files: Vec<PathBuf>,
+    profile: Option<String>,
+    sources: Sources,
+    builder: ConfigBuilder,
 }
 
 impl Config {
-    pub fn new() -> Result<Self, Box<dyn Error>> {
-        let mut config = Self::default();
-        let root = find_root()?;
-        config.root = root;
+    pub(crate) fn new(args_opt: ArgsOpt) -> Result<Self, Box<dyn Error>> {
+        ConfigBuilder::new().args(args_opt).build()
+    }
 
-        let file_opt = FileOpt::parse();
-        let args_opt = ArgsOpt::parse();
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::new()
+    }
 
-        let mut config = config.merge(file_opt, args_opt);
-        config.canonicalise();
-        Ok(config)
+    // CLI arguments and builder settings can't change while watchdog is running, so only the
+    // config files are read again
+    pub fn reload(&self) -> Result<Self, Box<dyn Error>> {
+        self.builder.build()
     }
 
-    fn merge(mut self, mut file_opt: FileOpt, mut args_opt: ArgsOpt) -> Self {
-        self.merge_exec(file_opt.take_exec(), args_opt.take_exec());
-        self.merge_exec_pre(file_opt.take_exec_pre(), args_opt.take_exec_pre());
-        self.merge_exclude(file_opt.take_exclude(), args_opt.take_exclude());
-        self.merge_extend(file_opt.take_extend(), args_opt.take_extend());
+    fn validate(&self) -> Result<(), String> {
+        if self.exec.command.is_empty() {
+            return Err("No command to execute, set one under [exec] or with --exec".into());
+        }
+        if !self.exec.origin.is_dir() {
+            return Err(format!(
+                "The exec origin {:?} is not a directory",
+                self.exec.origin
+            ));
+        }
 
-        self.canonicalise();
+        if let Some(exec_pre) = &self.exec_pre {
+            if !exec_pre.origin.is_dir() {
+                return Err(format!(
+                    "The exec_pre origin {:?} is not a directory",
+                    exec_pre.origin
+                ));
+            }
+            if exec_pre
+                .commands
+                .iter()
+                .any(|exec_pre_command| exec_pre_command.command.is_empty())
+            {
+                return Err("Every exec_pre command needs a command to execute".into());
+            }
+        }
 
-        self
+        parse_escape_key(self.watch.escape_key())?;
+
+        Ok(())
+    }
+
+    pub fn changes(&self, new_config: &Config) -> ConfigChanges {
+        ConfigChanges {
+            exec: self.exec != new_config.exec,
+            exec_pre: self.exec_pre != new_config.exec_pre,
+        }
+    }
+
+    // the CLI arguments go on top of the config files, already layered by ConfigBuilder
+    fn merge(mut self, mut file_opt: FileOpt, mut args_opt: ArgsOpt) -> Result<Self, ConfigError> {
+        self.config_files = file_opt.take_files();
+        self.merge_exec(file_opt.take_exec(), args_opt.take_exec());
+        self.merge_exec_pre(file_opt.take_exec_pre(), args_opt.take_exec_pre());
+        // CLI ignore rules are added to the ones of the config files unless asked otherwise
+        let (file_exclude, file_extend) = match args_opt.replace_exclude() {
+            true => (None, None),
+            false => (file_opt.take_exclude(), file_opt.take_extend()),
+        };
+        self.merge_exclude(file_exclude, args_opt.take_exclude());
+        self.merge_extend(file_extend, args_opt.take_extend())?;
+        self.merge_watch(file_opt.take_watch(), args_opt.take_watch());
+
+        Ok(self)
     }
 
     fn merge_exec(&mut self, file_exec: Option<FileOptExec>, args_exec: Option<ArgsOptExec>) {
         match (file_exec, args_exec) {
             (Some(file_exec), Some(args_exec)) => {
-                self.exec.merge_file_exec(file_exec);
-                self.exec.merge_args_exec(args_exec);
+                self.exec.merge_file_exec(file_exec, &mut self.sources);
+                self.exec.merge_args_exec(args_exec, &mut self.sources);
             }
-            (None, Some(args_exec)) => self.exec.merge_args_exec(args_exec),
-            (Some(file_exec), None) => self.exec.merge_file_exec(file_exec),
+            (None, Some(args_exec)) => self.exec.merge_args_exec(args_exec, &mut self.sources),
+            (Some(file_exec), None) => self.exec.merge_file_exec(file_exec, &mut self.sources),
             (None, None) => self.exec = Exec::default(),
         }
     }
@@ -76,14 +142,14 @@ impl Config {
         let mut exec_pre = ExecPre::default();
         match (file_exec_pre, args_exec_pre) {
             (Some(file_exec_pre), Some(args_exec_pre)) => {
-                exec_pre.merge_file_exec_pre(file_exec_pre);
-                exec_pre.merge_args_exec_pre(args_exec_pre);
+                exec_pre.merge_file_exec_pre(file_exec_pre, &mut self.sources);
+                exec_pre.merge_args_exec_pre(args_exec_pre, &mut self.sources);
             }
             (None, Some(args_exec_pre)) => {
-                ExecPre::merge_args_exec_pre(&mut exec_pre, args_exec_pre);
+                ExecPre::merge_args_exec_pre(&mut exec_pre, args_exec_pre, &mut self.sources);
             }
             (Some(file_exec_pre), None) => {
-                ExecPre::merge_file_exec_pre(&mut exec_pre, file_exec_pre);
+                ExecPre::merge_file_exec_pre(&mut exec_pre, file_exec_pre, &mut self.sources);
             }
             (None, None) => return self.exec_pre = None,
         }
@@ -98,11 +164,17 @@ impl Config {
     ) {
         match (file_exclude, args_exclude) {
             (Some(file_exclude), Some(args_exclude)) => {
-                self.exclude.merge_file_exclude(file_exclude);
-                self.exclude.merge_args_exclude(args_exclude);
+                self.exclude
+                    .merge_file_exclude(file_exclude, &mut self.sources);
+                self.exclude
+                    .merge_args_exclude(args_exclude, &mut self.sources);
             }
-            (None, Some(args_exclude)) => self.exclude.merge_args_exclude(args_exclude),
-            (Some(file_exclude), None) => self.exclude.merge_file_exclude(file_exclude),
+            (None, Some(args_exclude)) => self
+                .exclude
+                .merge_args_exclude(args_exclude, &mut self.sources),
+            (Some(file_exclude), None) => self
+                .exclude
+                .merge_file_exclude(file_exclude, &mut self.sources),
             (None, None) => self.exclude = Exclude::default(),
         }
     }
@@ -111,16 +183,50 @@ impl Config {
         &mut self,
         file_extend: Option<FileOptExtend>,
         args_extend: Option<ArgsOptExtend>,
-    ) {
+    ) -> Result<(), ConfigError> {
+        let registry = self.builder.extendables();
+        // the CLI ones, and the ones of the builder, are reported against the project config
+        let config_path = self.config_files.last().cloned().unwrap_or_default();
+        let args_err = |err| ConfigError::new(&config_path, err);
         match (file_extend, args_extend) {
             (Some(file_extend), Some(args_extend)) => {
-                self.extend.merge_file_extend(file_extend);
-                self.extend.merge_args_extend(args_extend);
+                self.extend.merge_file_extend(
+                    file_extend,
+                    &self.root,
+                    &config_path,
+                    registry,
+                    &mut self.sources,
+                )?;
+                self.extend
+                    .merge_args_extend(args_extend, &self.root, registry, &mut self.sources)
+                    .map_err(args_err)?;
             }
-            (None, Some(args_extend)) => self.extend.merge_args_extend(args_extend),
-            (Some(file_extend), None) => self.extend.merge_file_extend(file_extend),
+            (None, Some(args_extend)) => self
+                .extend
+                .merge_args_extend(args_extend, &self.root, registry, &mut self.sources)
+                .map_err(args_err)?,
+            (Some(file_extend), None) => self.extend.merge_file_extend(
+                file_extend,
+                &self.root,
+                &config_path,
+                registry,
+                &mut self.sources,
+            )?,
             (None, None) => self.extend = Extend::default(),
         }
+        Ok(())
+    }
+
+    fn merge_watch(&mut self, file_watch: Option<FileOptWatch>, args_watch: Option<ArgsOptWatch>) {
+        match (file_watch, args_watch) {
+            (Some(file_watch), Some(args_watch)) => {
+                self.watch.merge_file_watch(file_watch, &mut self.sources);
+                self.watch.merge_args_watch(args_watch, &mut self.sources);
+            }
+            (None, Some(args_watch)) => self.watch.merge_args_watch(args_watch, &mut self.sources),
+            (Some(file_watch), None) => self.watch.merge_file_watch(file_watch, &mut self.sources),
+            (None, None) => self.watch = Watch::default(),
+        }
     }
 
     pub fn to_extend(&self) -> &Extend {
@@ -131,10 +237,37 @@ impl Config {
         &self.exclude
     }
 
+    pub fn to_watch(&self) -> &Watch {
+        &self.watch
+    }
+
     pub fn root(&self) -> &PathBuf {
         &self.root
     }
 
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    pub fn config_files(&self) -> &Vec<PathBuf> {
+        &self.config_files
+    }
+
+    // package.json isn't a config file until it has a `watchdog` key, which can be added to it
+    // after the config was loaded
+    pub(crate) fn becomes_config_file(&self, path: &Path) -> bool {
+        path == self.root.join(PACKAGE_JSON)
+            && !self
+                .config_files
+                .iter()
+                .any(|config_file| config_file == path)
+            && has_package_json_config(path)
+    }
+
+    pub fn sources(&self) -> &Sources {
+        &self.sources
+    }
+
     pub fn to_exec(&self) -> &Exec {
         &self.exec
     }
@@ -152,36 +285,69 @@ impl Config {
 }
 
 #[derive(Debug, Default)]
+pub struct ConfigChanges {
+    exec: bool,
+    exec_pre: bool,
+}
+
+impl ConfigChanges {
+    // ignore rules are looked up on every event, so only changes to what's being run
+    // require the running process to be restarted
+    pub fn requires_restart(&self) -> bool {
+        self.exec || self.exec_pre
+    }
+
+    pub fn exec_pre(&self) -> bool {
+        self.exec_pre
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
 pub struct Exec {
     command: String,
     args: Vec<String>,
     origin: PathBuf,
+    interactive: bool,
 }
 
 impl Exec {
-    fn merge_file_exec(&mut self, mut file_exec: FileOptExec) {
+    fn merge_file_exec(&mut self, mut file_exec: FileOptExec, sources: &mut Sources) {
         if let Some(command) = file_exec.take_command() {
             self.command = command;
+            sources.set("exec.command", Source::File);
         }
         if let Some(args) = file_exec.take_args() {
             self.args = args;
+            sources.set("exec.args", Source::File);
         }
         if let Some(origin) = file_exec.take_origin() {
             self.origin = origin;
+            sources.set("exec.origin", Source::File);
+        }
+        if let Some(interactive) = file_exec.take_interactive() {
+            self.interactive = interactive;
+            sources.set("exec.interactive", Source::File);
         }
     }
 
-    fn merge_args_exec(&mut self, mut args_exec: ArgsOptExec) {
+    fn merge_args_exec(&mut self, mut args_exec: ArgsOptExec, sources: &mut Sources) {
         if let Some(command) = args_exec.take_exec() {
             let (command, args) = parse_command_string(command);
             if let Some(command) = command {
                 self.command = command;
+                sources.set("exec.command", Source::Cli);
             }
             self.args = args;
+            sources.set("exec.args", Source::Cli);
         }
         if let Some(origin) = args_exec.take_origin() {
             let origin = PathBuf::from_str(&origin).unwrap();
             self.origin = origin;
+            sources.set("exec.origin", Source::Cli);
+        }
+        if args_exec.interactive() {
+            self.interactive = true;
+            sources.set("exec.interactive", Source::Cli);
         }
     }
 
@@ -197,6 +363,11 @@ impl Exec {
         &self.origin
     }
 
+    // the terminal's input goes to the command rather than to watchdog's keys
+    pub fn interactive(&self) -> bool {
+        self.interactive
+    }
+
     fn canonicalise(&mut self, root: &Path) {
         let origin = fs::canonicalize(root.join(self.origin())).map_err(|err| {
             eprintln!(
@@ -212,7 +383,7 @@ impl Exec {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq)]
 pub struct ExecPre {
     origin: PathBuf,
     when: When,
@@ -220,7 +391,7 @@ pub struct ExecPre {
 }
 
 impl ExecPre {
-    fn merge_file_exec_pre(&mut self, mut file_exec_pre: FileOptExecPre) {
+    fn merge_file_exec_pre(&mut self, mut file_exec_pre: FileOptExecPre, sources: &mut Sources) {
         if let Some(commands) = file_exec_pre.take_commands() {
             self.commands = commands
                 .into_iter()
@@ -237,25 +408,29 @@ impl ExecPre {
                     exec_pre_command
                 })
                 .collect();
+            sources.set("exec_pre.commands", Source::File);
         }
         if let Some(when) = file_exec_pre.take_when() {
             self.when = match when {
                 FileOptWhen::Once => When::Once,
                 FileOptWhen::Always => When::Always,
             };
+            sources.set("exec_pre.when", Source::File);
         }
         if let Some(origin) = file_exec_pre.take_origin() {
             self.origin = origin;
+            sources.set("exec_pre.origin", Source::File);
         }
     }
 
-    fn merge_args_exec_pre(&mut self, mut args_exec_pre: ArgsOptExecPre) {
+    fn merge_args_exec_pre(&mut self, mut args_exec_pre: ArgsOptExecPre, sources: &mut Sources) {
         if let Some(commands) = args_exec_pre.take_exec_pre() {
             let commands: Result<Vec<ExecPreCommand>, _> =
                 commands.into_iter().map(String::try_into).collect();
 
             if let Ok(commands) = commands {
                 self.commands = commands;
+                sources.set("exec_pre.commands", Source::Cli);
             }
         }
 
@@ -263,12 +438,14 @@ impl ExecPre {
             self.when = match when {
                 ArgsOptWhen::Once => When::Once,
                 ArgsOptWhen::Always => When::Always,
-            }
+            };
+            sources.set("exec_pre.when", Source::Cli);
         }
 
         if let Some(origin) = args_exec_pre.take_origin_pre() {
             let origin = PathBuf::from_str(&origin).unwrap();
             self.origin = origin;
+            sources.set("exec_pre.origin", Source::Cli);
         }
     }
 
@@ -299,14 +476,24 @@ impl ExecPre {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq)]
+#[non_exhaustive]
 pub enum When {
     Once,
     #[default]
     Always,
 }
 
-#[derive(Debug, Default)]
+impl Display for When {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            When::Once => write!(f, "once"),
+            When::Always => write!(f, "always"),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
 pub struct ExecPreCommand {
     command: String,
     args: Vec<String>,
@@ -334,6 +521,16 @@ impl TryFrom<String> for ExecPreCommand {
 }
 
 impl ExecPreCommand {
+    pub fn new(
+        command: impl Into<String>,
+        args: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            command: command.into(),
+            args: args.into_iter().map(Into::into).collect(),
+        }
+    }
+
     pub fn command(&self) -> &str {
         &self.command
     }
@@ -349,12 +546,14 @@ pub struct Exclude {
 }
 
 impl Exclude {
-    fn merge_file_exclude(&mut self, file_exclude: FileOptExclude) {
+    fn merge_file_exclude(&mut self, file_exclude: FileOptExclude, sources: &mut Sources) {
         self.exclude_files = file_exclude.take_exclude_files();
+        sources.set("exclude.files", Source::File);
     }
 
-    fn merge_args_exclude(&mut self, args_exclude: Vec<String>) {
-        self.exclude_files = args_exclude;
+    fn merge_args_exclude(&mut self, args_exclude: Vec<String>, sources: &mut Sources) {
+        self.exclude_files.extend(args_exclude);
+        sources.add("exclude.files", Source::Cli);
     }
 
     pub fn to_exclude_files(&self) -> &Vec<String> {
@@ -364,26 +563,365 @@ impl Exclude {
 
 #[derive(Debug, Default)]
 pub struct Extend {
-    extendables: Vec<ExtendableType>,
+    extendables: Vec<BoxedExtendable>,
 }
 
 impl Extend {
-    fn merge_file_extend(&mut self, file_extend: FileOptExtend) {
-        self.extendables = file_extend.take_extendables();
+    fn merge_file_extend(
+        &mut self,
+        file_extend: FileOptExtend,
+        root: &Path,
+        config_path: &Path,
+        registry: &ExtendableRegistry,
+        sources: &mut Sources,
+    ) -> Result<(), ConfigError> {
+        self.extendables.clear();
+        for extendable in file_extend.take_extendables() {
+            self.add(
+                registry,
+                extendable.extendable_type(),
+                root,
+                Path::new(extendable.path()),
+            )
+            .map_err(|err| {
+                // the file listing it, unless it was added by the builder
+                ConfigError::new(extendable.config_path().unwrap_or(config_path), err)
+            })?;
+        }
+        sources.set("extend.extendables", Source::File);
+        Ok(())
     }
 
-    fn merge_args_extend(&mut self, args_extend: ArgsOptExtend) {
-        if let Ok(Extend { extendables }) = TryInto::<Extend>::try_into(args_extend) {
-            self.extendables = extendables
+    fn merge_args_extend(
+        &mut self,
+        mut args_extend: ArgsOptExtend,
+        root: &Path,
+        registry: &ExtendableRegistry,
+        sources: &mut Sources,
+    ) -> Result<(), String> {
+        if let (Some(opt_extendable_types), Some(opt_paths)) = (
+            args_extend.take_extendable_type(),
+            args_extend.take_extend(),
+        ) {
+            for (opt_path, opt_extendable_type) in opt_paths.into_iter().zip(opt_extendable_types) {
+                let extendable_type = match opt_extendable_type {
+                    ArgsOptExtendableType::Git => "git",
+                };
+                self.add(registry, extendable_type, root, Path::new(&opt_path))?;
+            }
+            sources.add("extend.extendables", Source::Cli);
         }
+        Ok(())
     }
 
-    pub fn to_extendables(&self) -> &Vec<ExtendableType> {
+    // an unknown type or a file that can't be loaded is an error in the config
+    fn add(
+        &mut self,
+        registry: &ExtendableRegistry,
+        extendable_type: &str,
+        root: &Path,
+        path: &Path,
+    ) -> Result<(), String> {
+        let factory = registry.get(extendable_type)?;
+        let extendable = factory(root, path).map_err(|err| {
+            format!("could not load the {extendable_type} ignore file {path:?}: {err}")
+        })?;
+        self.extendables.push(extendable);
+        Ok(())
+    }
+
+    pub fn to_extendables(&self) -> &[BoxedExtendable] {
         &self.extendables
     }
 }
 
-fn find_root() -> Result<PathBuf, Box<dyn Error>> {
+#[derive(Debug, Default)]
+pub struct Watch {
+    hash_contents: bool,
+    events: Option<Vec<WatchEvent>>,
+    reexec: bool,
+    clear: Option<bool>,
+    debounce: Option<Duration>,
+    keys: Option<bool>,
+    escape_key: Option<String>,
+    run_on_resume: bool,
+}
+
+impl Watch {
+    fn merge_file_watch(&mut self, mut file_watch: FileOptWatch, sources: &mut Sources) {
+        if let Some(hash_contents) = file_watch.take_hash_contents() {
+            self.hash_contents = hash_contents;
+            sources.set("watch.hash_contents", Source::File);
+        }
+        if let Some(reexec) = file_watch.take_reexec() {
+            self.reexec = reexec;
+            sources.set("watch.reexec", Source::File);
+        }
+        if let Some(clear) = file_watch.take_clear() {
+            self.clear = Some(clear);
+            sources.set("watch.clear", Source::File);
+        }
+        if let Some(debounce) = file_watch.take_debounce() {
+            self.debounce = Some(Duration::from_millis(debounce));
+            sources.set("watch.debounce", Source::File);
+        }
+        if let Some(keys) = file_watch.take_keys() {
+            self.keys = Some(keys);
+            sources.set("watch.keys", Source::File);
+        }
+        if let Some(escape_key) = file_watch.take_escape_key() {
+            self.escape_key = Some(escape_key);
+            sources.set("watch.escape_key", Source::File);
+        }
+        if let Some(run_on_resume) = file_watch.take_run_on_resume() {
+            self.run_on_resume = run_on_resume;
+            sources.set("watch.run_on_resume", Source::File);
+        }
+        if let Some(events) = file_watch.take_events() {
+            self.events = Some(
+                events
+                    .into_iter()
+                    .map(|event| match event {
+                        FileOptEventKind::Create => WatchEvent::Create,
+                        FileOptEventKind::Remove => WatchEvent::Remove,
+                        FileOptEventKind::Rename => WatchEvent::Rename,
+                        FileOptEventKind::Modify => WatchEvent::Modify,
+                        FileOptEventKind::ModifyData => WatchEvent::ModifyData,
+                        FileOptEventKind::ModifyMetadata => WatchEvent::ModifyMetadata,
+                        FileOptEventKind::Access => WatchEvent::Access,
+                    })
+                    .collect(),
+            );
+            sources.set("watch.events", Source::File);
+        }
+    }
+
+    fn merge_args_watch(&mut self, mut args_watch: ArgsOptWatch, sources: &mut Sources) {
+        if args_watch.reexec() {
+            self.reexec = true;
+            sources.set("watch.reexec", Source::Cli);
+        }
+        if args_watch.no_clear() {
+            self.clear = Some(false);
+            sources.set("watch.clear", Source::Cli);
+        }
+        if let Some(debounce) = args_watch.take_debounce() {
+            self.debounce = Some(Duration::from_millis(debounce));
+            sources.set("watch.debounce", Source::Cli);
+        }
+        if args_watch.no_keys() {
+            self.keys = Some(false);
+            sources.set("watch.keys", Source::Cli);
+        }
+        if let Some(escape_key) = args_watch.take_escape_key() {
+            self.escape_key = Some(escape_key);
+            sources.set("watch.escape_key", Source::Cli);
+        }
+        if args_watch.run_on_resume() {
+            self.run_on_resume = true;
+            sources.set("watch.run_on_resume", Source::Cli);
+        }
+        if let Some(events) = args_watch.take_events() {
+            self.events = Some(
+                events
+                    .into_iter()
+                    .map(|event| match event {
+                        ArgsOptEventKind::Create => WatchEvent::Create,
+                        ArgsOptEventKind::Remove => WatchEvent::Remove,
+                        ArgsOptEventKind::Rename => WatchEvent::Rename,
+                        ArgsOptEventKind::Modify => WatchEvent::Modify,
+                        ArgsOptEventKind::ModifyData => WatchEvent::ModifyData,
+                        ArgsOptEventKind::ModifyMetadata => WatchEvent::ModifyMetadata,
+                        ArgsOptEventKind::Access => WatchEvent::Access,
+                    })
+                    .collect(),
+            );
+            sources.set("watch.events", Source::Cli);
+        }
+    }
+
+    pub fn hash_contents(&self) -> bool {
+        self.hash_contents
+    }
+
+    pub fn reexec(&self) -> bool {
+        self.reexec
+    }
+
+    pub fn clear(&self) -> bool {
+        self.clear.unwrap_or(true)
+    }
+
+    pub fn debounce(&self) -> Duration {
+        self.debounce.unwrap_or(DEFAULT_DEBOUNCE)
+    }
+
+    pub fn keys(&self) -> bool {
+        self.keys.unwrap_or(true)
+    }
+
+    // e.g "ctrl-g"
+    pub fn escape_key(&self) -> &str {
+        self.escape_key.as_deref().unwrap_or(DEFAULT_ESCAPE_KEY)
+    }
+
+    // the byte a terminal sends for the escape key, validated when loading the config
+    pub fn escape_byte(&self) -> u8 {
+        parse_escape_key(self.escape_key()).unwrap_or(DEFAULT_ESCAPE_BYTE)
+    }
+
+    pub fn run_on_resume(&self) -> bool {
+        self.run_on_resume
+    }
+
+    pub fn events(&self) -> &[WatchEvent] {
+        self.events.as_deref().unwrap_or(&DEFAULT_WATCH_EVENTS)
+    }
+
+    pub fn triggers(&self, kind: &EventKind) -> bool {
+        self.events().iter().any(|event| event.matches(kind))
+    }
+}
+
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(500);
+
+const DEFAULT_ESCAPE_KEY: &str = "ctrl-g";
+
+const DEFAULT_ESCAPE_BYTE: u8 = 0x07;
+
+// ctrl-<letter>, except the ones the terminal already handles: interrupting, quitting,
+// suspending, pausing the output, and the backspace, tab, newline and enter keys
+fn parse_escape_key(key: &str) -> Result<u8, String> {
+    let letter = key
+        .strip_prefix("ctrl-")
+        .filter(|letter| letter.len() == 1)
+        .and_then(|letter| letter.chars().next())
+        .filter(char::is_ascii_lowercase)
+        .ok_or_else(|| format!("The escape key {key:?} should look like ctrl-g"))?;
+    if "cdhijmqsz".contains(letter) {
+        return Err(format!(
+            "The escape key {key:?} is already used by the terminal"
+        ));
+    }
+    Ok(letter as u8 - b'a' + 1)
+}
+
+const DEFAULT_WATCH_EVENTS: [WatchEvent; 4] = [
+    WatchEvent::Create,
+    WatchEvent::Remove,
+    WatchEvent::Rename,
+    WatchEvent::Modify,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WatchEvent {
+    Create,
+    Remove,
+    Rename,
+    Modify,
+    ModifyData,
+    ModifyMetadata,
+    Access,
+}
+
+impl Display for WatchEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            WatchEvent::Create => write!(f, "create"),
+            WatchEvent::Remove => write!(f, "remove"),
+            WatchEvent::Rename => write!(f, "rename"),
+            WatchEvent::Modify => write!(f, "modify"),
+            WatchEvent::ModifyData => write!(f, "modify:data"),
+            WatchEvent::ModifyMetadata => write!(f, "modify:metadata"),
+            WatchEvent::Access => write!(f, "access"),
+        }
+    }
+}
+
+impl WatchEvent {
+    fn matches(&self, kind: &EventKind) -> bool {
+        match (self, kind) {
+            (WatchEvent::Create, EventKind::Create(_)) => true,
+            (WatchEvent::Remove, EventKind::Remove(_)) => true,
+            (WatchEvent::Rename, EventKind::Modify(ModifyKind::Name(_))) => true,
+            (WatchEvent::Modify, EventKind::Modify(modify_kind)) => {
+                !matches!(modify_kind, ModifyKind::Name(_))
+            }
+            // not every platform can tell what kind of modification happened,
+            // so treat an unknown one as a data change rather than missing saves
+            (
+                WatchEvent::ModifyData,
+                EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Any | ModifyKind::Other),
+            ) => true,
+            (WatchEvent::ModifyMetadata, EventKind::Modify(ModifyKind::Metadata(_))) => true,
+            (WatchEvent::Access, EventKind::Access(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+// where each setting of the effective config came from, as shown by `watchdog check`
+#[derive(Debug, Default)]
+pub struct Sources {
+    sources: BTreeMap<&'static str, Source>,
+}
+
+impl Sources {
+    fn set(&mut self, key: &'static str, source: Source) {
+        self.sources.insert(key, source);
+    }
+
+    // for lists that the CLI adds to rather than replaces
+    fn add(&mut self, key: &'static str, source: Source) {
+        let source = match self.get(key) {
+            Source::Default => source,
+            _ => Source::FileAndCli,
+        };
+        self.set(key, source);
+    }
+
+    // ConfigBuilder settings are layered into the config files, so they're told apart once merged
+    fn set_builder(&mut self, key: &'static str, adds_to_file: bool) {
+        let source = match (self.get(key), adds_to_file) {
+            (Source::File, false) => Source::Builder,
+            (Source::File, true) => Source::FileAndBuilder,
+            (source, _) => source,
+        };
+        self.set(key, source);
+    }
+
+    pub fn get(&self, key: &str) -> Source {
+        self.sources.get(key).copied().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum Source {
+    #[default]
+    Default,
+    File,
+    Builder,
+    FileAndBuilder,
+    Cli,
+    FileAndCli,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File => write!(f, "file"),
+            Source::Builder => write!(f, "builder"),
+            Source::FileAndBuilder => write!(f, "file + builder"),
+            Source::Cli => write!(f, "cli"),
+            Source::FileAndCli => write!(f, "file + cli"),
+        }
+    }
+}
+
+pub fn find_root() -> Result<PathBuf, Box<dyn Error>> {
     let mut root_path = None;
     if let Ok(git_root_path) = Command::new("git")
         .args(["rev-parse", "--show-toplevel"])
@@ -416,38 +954,210 @@ fn parse_command_string(command: String) -> (Option<String>, Vec<String>) {
     (Some(command), args)
 }
 
-impl TryFrom<ArgsOptExtend> for Extend {
-    type Error = String;
+#[cfg(test)]
+mod tests {
+    use super::{parse_escape_key, Config, WatchEvent};
+    use notify::{
+        event::{
+            AccessKind, CreateKind, DataChange, MetadataKind, ModifyKind, RemoveKind, RenameMode,
+        },
+        EventKind,
+    };
+    use std::{fs, path::Path};
+    use tempfile::TempDir;
+
+    #[test]
+    fn watch_events_match_their_kinds() {
+        let rename_from = EventKind::Modify(ModifyKind::Name(RenameMode::From));
+        let data = EventKind::Modify(ModifyKind::Data(DataChange::Content));
+        let metadata = EventKind::Modify(ModifyKind::Metadata(MetadataKind::Any));
+        let unknown = EventKind::Modify(ModifyKind::Any);
+        let cases = [
+            (
+                WatchEvent::Create,
+                EventKind::Create(CreateKind::File),
+                true,
+            ),
+            (
+                WatchEvent::Create,
+                EventKind::Remove(RemoveKind::File),
+                false,
+            ),
+            (
+                WatchEvent::Remove,
+                EventKind::Remove(RemoveKind::Folder),
+                true,
+            ),
+            // a file moved out of the tree is reported as a lone rename-from
+            (WatchEvent::Remove, rename_from, false),
+            (WatchEvent::Rename, rename_from, true),
+            (WatchEvent::Rename, data, false),
+            (WatchEvent::Modify, data, true),
+            (WatchEvent::Modify, metadata, true),
+            (WatchEvent::Modify, rename_from, false),
+            (WatchEvent::ModifyData, data, true),
+            (WatchEvent::ModifyData, unknown, true),
+            (WatchEvent::ModifyData, metadata, false),
+            (WatchEvent::ModifyMetadata, metadata, true),
+            (WatchEvent::ModifyMetadata, data, false),
+            (WatchEvent::Access, EventKind::Access(AccessKind::Any), true),
+            (WatchEvent::Access, EventKind::Any, false),
+        ];
+
+        for (watch_event, kind, matches) in cases {
+            assert_eq!(
+                watch_event.matches(&kind),
+                matches,
+                "{watch_event} {kind:?}"
+            );
+        }
+    }
 
-    fn try_from(mut value: ArgsOptExtend) -> Result<Self, Self::Error> {
-        if let (Some(opt_extendable_types), Some(opt_paths)) =
-            (value.take_extendable_type(), value.take_extend())
-        {
-            let extendables = opt_paths
-                .into_iter()
-                .zip(opt_extendable_types)
-                .map(
-                    |(opt_path, opt_extendable_type)| match opt_extendable_type {
-                        ArgsOptExtendableType::Git => {
-                            let root = find_root().unwrap_or_default();
-                            let mut builder = GitignoreBuilder::new(&root);
-                            let err = builder.add(root.join(opt_path));
-                            if let Some(err) = err {
-                                return Err(err.to_string());
-                            }
-                            let git = builder
-                                .build()
-                                .map_err(|err| format!("Failed to build Gitignore: {err}"))?;
-
-                            Ok(ExtendableType::Git(GitignoreSerde(git)))
-                        }
-                    },
-                )
-                .collect::<Result<Vec<ExtendableType>, String>>()?;
-
-            return Ok(Self { extendables });
-        }
-
-        Err("Could not convert OptExtendFiles into Extend".into())
+    fn build(root: &Path, config: &str) -> Result<Config, String> {
+        fs::write(root.join("watchdog.toml"), config).unwrap();
+        Config::builder()
+            .root(root)
+            .build()
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn validates_what_is_run() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        let cases = [
+            ("[exec]\nargs = [ \"run\" ]\n", "No command to execute"),
+            (
+                "[exec]\ncommand = \"cargo\"\norigin = \"missing\"\n",
+                "The exec origin",
+            ),
+            (
+                "[exec]\ncommand = \"cargo\"\n\n[exec_pre]\norigin = \"missing\"\ncommands = [ { command = \"make\" } ]\n",
+                "The exec_pre origin",
+            ),
+            (
+                "[exec]\ncommand = \"cargo\"\n\n[exec_pre]\ncommands = [ { command = \"\" } ]\n",
+                "Every exec_pre command needs a command",
+            ),
+        ];
+
+        for (config, error) in cases {
+            let err = build(root, config).unwrap_err();
+            assert!(err.contains(error), "{config}: {err}");
+        }
+        assert!(build(root, "[exec]\ncommand = \"cargo\"\n").is_ok());
+    }
+
+    #[test]
+    fn only_changes_to_what_is_run_require_a_restart() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        let exec_pre = "[exec_pre]\ncommands = [ { command = \"make\" } ]\n";
+        let config = build(root, &format!("[exec]\ncommand = \"cargo\"\n\n{exec_pre}")).unwrap();
+        let changes = |new_config: &str| config.changes(&build(root, new_config).unwrap());
+
+        let unchanged = changes(&format!("[exec]\ncommand = \"cargo\"\n\n{exec_pre}"));
+        assert!(!unchanged.requires_restart());
+        let exclude = changes(&format!(
+            "[exec]\ncommand = \"cargo\"\n\n{exec_pre}\n[exclude]\nfiles = [ {{ path = \"target\" }} ]\n"
+        ));
+        assert!(!exclude.requires_restart());
+        let exec = changes(&format!(
+            "[exec]\ncommand = \"cargo\"\nargs = [ \"test\" ]\n\n{exec_pre}"
+        ));
+        assert!(exec.requires_restart() && !exec.exec_pre());
+        let removed_exec_pre = changes("[exec]\ncommand = \"cargo\"\n");
+        assert!(removed_exec_pre.requires_restart() && removed_exec_pre.exec_pre());
+    }
+
+    #[test]
+    fn rejects_git_ignore_files_that_cannot_be_loaded() {
+        let dir = TempDir::new().unwrap();
+        let err = build(
+            dir.path(),
+            "[exec]\ncommand = \"cargo\"\n\n[extend]\nextendables = [ { extendable_type = \"git\", path = \".gitignore\" } ]\n",
+        )
+        .unwrap_err();
+
+        assert!(
+            err.contains("could not load the git ignore file \".gitignore\""),
+            "{err}"
+        );
+        assert!(err.contains("watchdog.toml"), "{err}");
+    }
+
+    #[test]
+    fn loads_the_legacy_run_and_build_tables() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        let exec_pre = |config: &Config| {
+            config.to_exec_pre().as_ref().map(|exec_pre| {
+                let commands = exec_pre.commands().iter();
+                let commands: Vec<&str> = commands.map(|command| command.command()).collect();
+                format!("{} {}", exec_pre.when(), commands.join(" "))
+            })
+        };
+
+        let precompiled = build(root, "[run]\ncommand = \"cargo\"\nargs = [ \"run\" ]\nprecompile = true\n\n[build]\ncommand = \"make\"\n").unwrap();
+        assert_eq!(precompiled.to_exec().command(), "cargo");
+        assert_eq!(precompiled.to_exec().args(), &["run"]);
+        assert_eq!(exec_pre(&precompiled), Some("always make".into()));
+
+        // [build] only ever ran with precompile
+        let not_precompiled = build(
+            root,
+            "[run]\ncommand = \"cargo\"\n\n[build]\ncommand = \"make\"\n",
+        )
+        .unwrap();
+        assert_eq!(exec_pre(&not_precompiled), None);
+
+        let both = build(
+            root,
+            "[run]\ncommand = \"npm\"\n\n[exec]\ncommand = \"cargo\"\n",
+        )
+        .unwrap();
+        assert_eq!(both.to_exec().command(), "cargo");
+
+        let err = build(root, "[run]\ncommand = \"cargo\"\nprecompile = true\n").unwrap_err();
+        assert!(err.contains("requires a [build] table"), "{err}");
+    }
+
+    #[test]
+    fn package_json_becomes_a_config_file_with_a_watchdog_key() {
+        let dir = TempDir::new().unwrap();
+        let package_json = dir.path().join("package.json");
+        fs::write(&package_json, r#"{ "name": "app" }"#).unwrap();
+        let config = Config::builder()
+            .root(dir.path())
+            .exec("npm", ["start"])
+            .build()
+            .unwrap();
+        let package_json = config.root().join("package.json");
+        assert!(!config.config_files().contains(&package_json));
+        assert!(!config.becomes_config_file(&package_json));
+
+        fs::write(&package_json, r#"{ "name": "app", "watchdog": {} }"#).unwrap();
+        assert!(config.becomes_config_file(&package_json));
+        assert!(!config.becomes_config_file(&config.root().join("watchdog.json")));
+        let reloaded = config.reload().unwrap();
+        assert!(reloaded.config_files().contains(&package_json));
+        assert!(!reloaded.becomes_config_file(&package_json));
+    }
+
+    #[test]
+    fn parses_escape_keys_the_terminal_leaves_alone() {
+        assert_eq!(parse_escape_key("ctrl-a"), Ok(0x01));
+        assert_eq!(parse_escape_key("ctrl-g"), Ok(0x07));
+        assert_eq!(
+            parse_escape_key("ctrl-z").unwrap_err(),
+            "The escape key \"ctrl-z\" is already used by the terminal"
+        );
+        for key in "cdhijmqsz".chars() {
+            assert!(parse_escape_key(&format!("ctrl-{key}")).is_err(), "{key}");
+        }
+        for key in ["g", "ctrl-G", "ctrl-gg", "ctrl-", "ctrl-1", "alt-g"] {
+            let err = parse_escape_key(key).unwrap_err();
+            assert!(err.contains("should look like ctrl-g"), "{key}: {err}");
+        }
     }
 }
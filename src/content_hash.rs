@@ -0,0 +1,199 @@
+use crate::{config::Config, watch::should_ignore_path};
+use notify::Event;
+use std::{
+    collections::HashMap,
+    fs,
+    hash::{DefaultHasher, Hasher},
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Default)]
+pub struct ContentHashes {
+    hashes: HashMap<PathBuf, u64>,
+}
+
+impl ContentHashes {
+    pub fn new(config: &Config) -> Self {
+        let mut content_hashes = Self::default();
+        if config.to_watch().hash_contents() {
+            content_hashes.prefill(config);
+        }
+
+        content_hashes
+    }
+
+    // hashes every file under root that watchdog would react to, so that the very first save
+    // of an untouched file can already be compared against something
+    pub fn prefill(&mut self, config: &Config) {
+        self.hashes.clear();
+        self.prefill_dir(config, config.root());
+    }
+
+    fn prefill_dir(&mut self, config: &Config, dir: &Path) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+            if should_ignore_path(config, &path, is_dir) {
+                continue;
+            }
+
+            if is_dir {
+                self.prefill_dir(config, &path);
+            } else if let Some(hash) = hash_file(&path) {
+                self.hashes.insert(path, hash);
+            }
+        }
+    }
+
+    // returns true only if the file was hashed before and its contents are still the same;
+    // unknown or unreadable files are always considered as changed
+    pub fn is_unchanged(&mut self, path: &Path) -> bool {
+        match hash_file(path) {
+            Some(hash) => self.hashes.insert(path.to_path_buf(), hash) == Some(hash),
+            None => {
+                self.hashes.remove(path);
+                false
+            }
+        }
+    }
+
+    pub fn update(&mut self, path: &Path) {
+        match hash_file(path) {
+            Some(hash) => self.hashes.insert(path.to_path_buf(), hash),
+            None => self.hashes.remove(path),
+        };
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.hashes
+            .retain(|hashed_path, _| !hashed_path.starts_with(path));
+    }
+
+    // rehashes the paths of an event that was dropped rather than handled, e.g during the
+    // debounce time, so that the next change isn't compared against a stale hash
+    pub fn refresh(&mut self, config: &Config, event: &Event) {
+        if !config.to_watch().hash_contents() {
+            return;
+        }
+
+        for path in &event.paths {
+            if path.is_file() {
+                if !should_ignore_path(config, path, false) {
+                    self.update(path);
+                }
+            } else {
+                self.remove(path);
+            }
+        }
+    }
+}
+
+fn hash_file(path: &Path) -> Option<u64> {
+    if !path.is_file() {
+        return None;
+    }
+
+    let contents = fs::read(path).ok()?;
+    let mut hasher = DefaultHasher::new();
+    hasher.write(&contents);
+
+    Some(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ContentHashes;
+    use crate::config::Config;
+    use notify::{
+        event::{DataChange, ModifyKind},
+        Event, EventKind,
+    };
+    use std::{fs, path::PathBuf};
+    use tempfile::TempDir;
+
+    fn project(config: &str) -> (TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::write(
+            root.join("watchdog.toml"),
+            format!("[exec]\ncommand = \"cargo\"\n\n{config}"),
+        )
+        .unwrap();
+        (dir, root)
+    }
+
+    #[test]
+    fn compares_against_the_last_contents() {
+        let (_dir, root) = project("");
+        let file = root.join("a.rs");
+        let mut content_hashes = ContentHashes::default();
+
+        fs::write(&file, "a").unwrap();
+        // unknown files are considered as changed, and hashed from then on
+        assert!(!content_hashes.is_unchanged(&file));
+        assert!(content_hashes.is_unchanged(&file));
+        fs::write(&file, "b").unwrap();
+        assert!(!content_hashes.is_unchanged(&file));
+        fs::write(&file, "a").unwrap();
+        assert!(!content_hashes.is_unchanged(&file));
+    }
+
+    #[test]
+    fn forgets_removed_and_renamed_files() {
+        let (_dir, root) = project("");
+        fs::create_dir(root.join("src")).unwrap();
+        let (file, renamed) = (root.join("src/a.rs"), root.join("b.rs"));
+        let mut content_hashes = ContentHashes::default();
+        fs::write(&file, "a").unwrap();
+        content_hashes.update(&file);
+
+        // removing a directory forgets the files under it
+        content_hashes.remove(&root.join("src"));
+        assert!(!content_hashes.is_unchanged(&file));
+
+        fs::rename(&file, &renamed).unwrap();
+        content_hashes.remove(&file);
+        assert!(!content_hashes.is_unchanged(&renamed));
+        assert!(content_hashes.is_unchanged(&renamed));
+        fs::write(&file, "a").unwrap();
+        assert!(!content_hashes.is_unchanged(&file));
+    }
+
+    #[test]
+    fn prefills_files_that_are_not_ignored() {
+        let (_dir, root) = project(
+            "[exclude]\nfiles = [ { path = \"target\" } ]\n\n[watch]\nhash_contents = true\n",
+        );
+        fs::create_dir(root.join("target")).unwrap();
+        fs::write(root.join("a.rs"), "a").unwrap();
+        fs::write(root.join("target/app"), "app").unwrap();
+        let config = Config::builder().root(&root).build().unwrap();
+
+        let mut content_hashes = ContentHashes::new(&config);
+
+        assert!(content_hashes.is_unchanged(&root.join("a.rs")));
+        assert!(!content_hashes.is_unchanged(&root.join("target/app")));
+    }
+
+    #[test]
+    fn refreshes_the_hashes_of_dropped_events() {
+        let (_dir, root) = project("[watch]\nhash_contents = true\n");
+        let file = root.join("a.rs");
+        fs::write(&file, "a").unwrap();
+        let config = Config::builder().root(&root).build().unwrap();
+        let mut content_hashes = ContentHashes::new(&config);
+
+        // changed within the debounce time, then reverted
+        fs::write(&file, "b").unwrap();
+        let event = Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content)))
+            .add_path(file.clone());
+        content_hashes.refresh(&config, &event);
+        fs::write(&file, "a").unwrap();
+
+        assert!(!content_hashes.is_unchanged(&file));
+    }
+}
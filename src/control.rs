@@ -0,0 +1,66 @@
+use std::sync::mpsc::Sender;
+
+// what a running Watchdog can be asked to do besides reacting to file changes, e.g from the
+// keyboard; more kinds may be added in minor releases
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Control {
+    Restart,
+    Clear,
+    Pause,
+    Resume,
+    TogglePause,
+    ReloadConfig,
+    // stop the command gracefully, then return from `Watchdog::run`
+    Quit,
+    Interrupt,
+    Terminate,
+}
+
+impl Control {
+    pub(crate) fn stops(self) -> Option<Stopped> {
+        match self {
+            Control::Quit => Some(Stopped::Quit),
+            Control::Interrupt => Some(Stopped::Interrupted),
+            Control::Terminate => Some(Stopped::Terminated),
+            _ => None,
+        }
+    }
+}
+
+// why `Watchdog::run` returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Stopped {
+    Quit,
+    Interrupted,
+    Terminated,
+}
+
+impl Stopped {
+    // the one of the binary, following the shell convention of 128 + the signal number
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Stopped::Quit => 0,
+            Stopped::Interrupted => 130,
+            Stopped::Terminated => 143,
+        }
+    }
+}
+
+// handed out by `Watchdog::controller`, controls can be sent from any thread
+#[derive(Debug, Clone)]
+pub struct Controller {
+    tx: Sender<Control>,
+}
+
+impl Controller {
+    pub(crate) fn new(tx: Sender<Control>) -> Self {
+        Self { tx }
+    }
+
+    // false once the Watchdog is gone
+    pub fn send(&self, control: Control) -> bool {
+        self.tx.send(control).is_ok()
+    }
+}
@@ -0,0 +1,51 @@
+use notify::{Config as NotifyConfig, Event, RecommendedWatcher, RecursiveMode, Result, Watcher};
+use std::{
+    path::Path,
+    sync::mpsc::{channel, Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+// where the file events a Watchdog reacts to come from, the file system unless running tests
+pub trait EventSource: Send {
+    fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()>;
+
+    // `Disconnected` once no more events can come, which stops the Watchdog
+    fn recv_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> std::result::Result<Result<Event>, RecvTimeoutError>;
+
+    // drops the events that came in while the command was being restarted, returning them
+    fn drain(&mut self) -> Vec<Event>;
+}
+
+pub struct NotifyEventSource {
+    watcher: RecommendedWatcher,
+    rx: Receiver<Result<Event>>,
+}
+
+impl NotifyEventSource {
+    pub fn new() -> Result<Self> {
+        let (tx, rx) = channel();
+        let watcher =
+            RecommendedWatcher::new(tx, NotifyConfig::default().with_compare_contents(true))?;
+        Ok(Self { watcher, rx })
+    }
+}
+
+impl EventSource for NotifyEventSource {
+    fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+        self.watcher.watch(path, recursive_mode)
+    }
+
+    fn recv_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> std::result::Result<Result<Event>, RecvTimeoutError> {
+        self.rx.recv_timeout(timeout)
+    }
+
+    fn drain(&mut self) -> Vec<Event> {
+        self.rx.try_iter().filter_map(|res| res.ok()).collect()
+    }
+}
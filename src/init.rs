@@ -0,0 +1,216 @@
+use crate::config::{existing_config_files, find_root, ArgsOptInit};
+use std::{fmt::Write, fs, path::Path};
+
+struct Project {
+    name: &'static str,
+    marker: &'static str,
+    exec: (&'static str, &'static [&'static str]),
+    exec_pre_when: &'static str,
+    exec_pre: &'static [(&'static str, &'static [&'static str])],
+    exclude: &'static [&'static str],
+}
+
+// checked in order, the first marker file found in the root project wins
+const PROJECTS: [Project; 5] = [
+    Project {
+        name: "Rust",
+        marker: "Cargo.toml",
+        exec: ("cargo", &["run"]),
+        exec_pre_when: "always",
+        exec_pre: &[("cargo", &["build"])],
+        exclude: &[".git", "target"],
+    },
+    Project {
+        name: "Node.js",
+        marker: "package.json",
+        exec: ("npm", &["start"]),
+        exec_pre_when: "once",
+        exec_pre: &[("npm", &["install"])],
+        exclude: &[".git", "node_modules"],
+    },
+    Project {
+        name: "Go",
+        marker: "go.mod",
+        exec: ("go", &["run", "."]),
+        exec_pre_when: "always",
+        exec_pre: &[("go", &["build", "./..."])],
+        exclude: &[".git"],
+    },
+    Project {
+        name: "Python",
+        marker: "pyproject.toml",
+        exec: ("python", &["main.py"]),
+        exec_pre_when: "once",
+        exec_pre: &[("pip", &["install", "-e", "."])],
+        exclude: &[".git", "__pycache__", ".venv"],
+    },
+    Project {
+        name: "Make",
+        marker: "Makefile",
+        exec: ("make", &["run"]),
+        exec_pre_when: "always",
+        exec_pre: &[("make", &[])],
+        exclude: &[".git"],
+    },
+];
+
+const UNKNOWN_PROJECT: Project = Project {
+    name: "generic",
+    marker: "",
+    exec: ("echo", &["replace me with the command to run"]),
+    exec_pre_when: "always",
+    exec_pre: &[],
+    exclude: &[".git"],
+};
+
+pub fn init(args_init: ArgsOptInit) -> i32 {
+    let root = match find_root() {
+        Ok(root) => root,
+        Err(err) => {
+            eprintln!("{err}");
+            return 1;
+        }
+    };
+
+    let config_path = root.join("watchdog.toml");
+    if config_path.exists() && !args_init.force() {
+        eprintln!("{config_path:?} already exists, use --force to overwrite it");
+        return 1;
+    }
+    // --force only overwrites watchdog.toml, another config file would conflict with it
+    if let Some(other_config_path) = existing_config_files(&root)
+        .into_iter()
+        .find(|other_config_path| other_config_path != &config_path)
+    {
+        eprintln!("{other_config_path:?} already configures watchdog");
+        return 1;
+    }
+
+    let project = PROJECTS
+        .iter()
+        .find(|project| root.join(project.marker).is_file())
+        .unwrap_or(&UNKNOWN_PROJECT);
+
+    if let Err(err) = fs::write(&config_path, render(project, &root)) {
+        eprintln!("Could not write {config_path:?}: {err}");
+        return 1;
+    }
+
+    println!(
+        "Created {config_path:?} for a {} project, run `watchdog check` to see the effective config",
+        project.name
+    );
+    0
+}
+
+fn render(project: &Project, root: &Path) -> String {
+    let mut config = String::new();
+    let _ = writeln!(
+        config,
+        "# watchdog.toml generated by `watchdog init` for a {} project\n",
+        project.name
+    );
+
+    if root.join(".gitignore").is_file() {
+        config.push_str("# ignore files that are ignored by git as well\n");
+        config.push_str("[extend]\nextendables = [\n");
+        config.push_str("  { extendable_type = \"git\", path = \".gitignore\" },\n]\n\n");
+    }
+
+    config.push_str("# paths containing any of these are never watched\n");
+    config.push_str("[exclude]\nfiles = [\n");
+    for exclude in project.exclude {
+        let _ = writeln!(config, "  {{ path = {exclude:?} }},");
+    }
+    config.push_str("]\n\n");
+
+    let (command, args) = project.exec;
+    config.push_str("# the command that's (re)started on every change\n");
+    let _ = writeln!(
+        config,
+        "[exec]\ncommand = {command:?}\nargs = {}\n",
+        render_args(args)
+    );
+
+    config.push_str(
+        "# commands that run sequentially before [exec], either \"once\" or \"always\"\n",
+    );
+    if project.exec_pre.is_empty() {
+        config.push_str("# [exec_pre]\n# when = \"always\"\n# commands = [\n");
+        config.push_str("#   { command = \"make\", args = [ \"build\" ] },\n# ]\n");
+    } else {
+        let _ = writeln!(
+            config,
+            "[exec_pre]\nwhen = {:?}\ncommands = [",
+            project.exec_pre_when
+        );
+        for (command, args) in project.exec_pre {
+            let _ = writeln!(
+                config,
+                "  {{ command = {command:?}, args = {} }},",
+                render_args(args)
+            );
+        }
+        config.push_str("]\n");
+    }
+
+    config
+}
+
+fn render_args(args: &[&str]) -> String {
+    if args.is_empty() {
+        return "[]".into();
+    }
+
+    let args: Vec<String> = args.iter().map(|arg| format!("{arg:?}")).collect();
+    format!("[ {} ]", args.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, PROJECTS, UNKNOWN_PROJECT};
+    use crate::config::Config;
+    use std::fs;
+
+    #[test]
+    fn renders_configs_that_load() {
+        for project in PROJECTS.iter().chain([&UNKNOWN_PROJECT]) {
+            for gitignore in [false, true] {
+                let dir = tempfile::tempdir().unwrap();
+                if gitignore {
+                    fs::write(dir.path().join(".gitignore"), "/out\n").unwrap();
+                }
+                fs::write(
+                    dir.path().join("watchdog.toml"),
+                    render(project, dir.path()),
+                )
+                .unwrap();
+
+                let config = Config::builder().root(dir.path()).build().unwrap();
+                let (command, args) = project.exec;
+                assert_eq!(config.to_exec().command(), command, "{}", project.name);
+                assert_eq!(config.to_exec().args(), args, "{}", project.name);
+                let exec_pre: Vec<(&str, Vec<&str>)> = config
+                    .to_exec_pre()
+                    .iter()
+                    .flat_map(|exec_pre| exec_pre.commands())
+                    .map(|exec_pre_command| {
+                        let args = exec_pre_command.args().iter().map(String::as_str);
+                        (exec_pre_command.command(), args.collect())
+                    })
+                    .collect();
+                let expected: Vec<(&str, Vec<&str>)> = project
+                    .exec_pre
+                    .iter()
+                    .map(|(command, args)| (*command, args.to_vec()))
+                    .collect();
+                assert_eq!(exec_pre, expected, "{}", project.name);
+                assert_eq!(config.to_exclude().to_exclude_files(), project.exclude);
+                assert_eq!(
+                    config.to_extend().to_extendables().len(),
+                    gitignore as usize
+                );
+            }
+        }
+    }
+}
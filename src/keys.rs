@@ -0,0 +1,295 @@
+use crate::control::{Control, Controller};
+use std::{
+    io::{self, IsTerminal, Read, Write},
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+};
+
+// read as a key rather than interrupting watchdog, so that it quits gracefully
+const CTRL_C: u8 = 0x03;
+
+// reads the keys typed in the terminal until watchdog exits, the terminal is set back to how it
+// was once dropped
+pub(crate) struct Keys {
+    _mode: terminal::Mode,
+}
+
+impl Keys {
+    // `None` when stdin isn't a terminal, e.g when piped or run from a script
+    pub(crate) fn start(controller: Controller, input: SharedInput, echo: bool) -> Option<Self> {
+        if !io::stdin().is_terminal() {
+            return None;
+        }
+        let mode = terminal::Mode::cbreak(echo)
+            .inspect_err(|err| eprintln!("Could not read keys from the terminal: {err}"))
+            .ok()?;
+        thread::spawn(move || read_keys(controller, input));
+        Some(Self { _mode: mode })
+    }
+}
+
+// the stdin of an interactive command, which gets what's typed unless it follows the escape key
+#[derive(Clone, Default)]
+pub(crate) struct SharedInput {
+    child: Arc<Mutex<Option<ChildInput>>>,
+}
+
+struct ChildInput {
+    stdin: Box<dyn Write + Send>,
+    escape: u8,
+}
+
+impl SharedInput {
+    pub(crate) fn set(&self, stdin: Option<Box<dyn Write + Send>>, escape: u8) {
+        *self.lock() = stdin.map(|stdin| ChildInput { stdin, escape });
+    }
+
+    fn escape(&self) -> Option<u8> {
+        self.lock().as_ref().map(|child| child.escape)
+    }
+
+    // true if the byte went to the command rather than being a key for watchdog
+    fn forward(&self, byte: u8, escaped: &mut bool) -> bool {
+        let mut input = self.lock();
+        let Some(child) = input.as_mut() else {
+            return false;
+        };
+        let forward = match (*escaped, byte == child.escape) {
+            (false, true) => {
+                *escaped = true;
+                return true;
+            }
+            // pressing the escape key twice sends it to the command, and pressing it before
+            // ctrl-c sends ctrl-c, which otherwise interrupts watchdog
+            (true, true) => true,
+            (true, false) => byte == CTRL_C,
+            (false, false) => byte != CTRL_C,
+        };
+        *escaped = false;
+        if !forward {
+            return false;
+        }
+
+        if child
+            .stdin
+            .write_all(&[byte])
+            .and_then(|()| child.stdin.flush())
+            .is_err()
+        {
+            // the command stopped reading, the keys are watchdog's again
+            *input = None;
+        }
+        true
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<ChildInput>> {
+        self.child.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Key {
+    Control(Control),
+    Help,
+}
+
+fn read_keys(controller: Controller, input: SharedInput) {
+    let mut escaped = false;
+    for byte in io::stdin().lock().bytes() {
+        let Ok(byte) = byte else {
+            return;
+        };
+        if input.forward(byte, &mut escaped) {
+            continue;
+        }
+
+        match key(byte) {
+            Some(Key::Control(control)) if !controller.send(control) => return,
+            Some(Key::Help) => print_help(input.escape()),
+            _ => {}
+        }
+    }
+}
+
+// `None` for the keys watchdog doesn't use
+fn key(byte: u8) -> Option<Key> {
+    let control = match byte {
+        b'r' => Control::Restart,
+        b'c' => Control::Clear,
+        b'p' => Control::TogglePause,
+        b'q' => Control::Quit,
+        CTRL_C => Control::Interrupt,
+        b'?' => return Some(Key::Help),
+        _ => return None,
+    };
+    Some(Key::Control(control))
+}
+
+fn print_help(escape: Option<u8>) {
+    if let Some(escape) = escape {
+        let letter = char::from(escape + b'a' - 1);
+        println!("the command is interactive, press ctrl-{letter} before a key");
+        println!("  ctrl-{letter} ctrl-c  send ctrl-c to the command");
+    }
+    println!("  r  restart the command");
+    println!("  c  clear the screen");
+    println!("  p  pause or resume watching");
+    println!("  q  quit");
+    println!("  ?  show the keys");
+}
+
+#[cfg(unix)]
+mod terminal {
+    use std::{io, mem::MaybeUninit};
+
+    // the settings of the terminal before watchdog changed them
+    pub(super) struct Mode(libc::termios);
+
+    impl Mode {
+        // keys are read as soon as they're typed, and ctrl-c is read as a key
+        pub(super) fn cbreak(echo: bool) -> io::Result<Self> {
+            let mut termios = MaybeUninit::uninit();
+            if unsafe { libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let saved = unsafe { termios.assume_init() };
+
+            let mut cbreak = saved;
+            cbreak.c_lflag &= !(libc::ICANON | libc::ISIG);
+            if !echo {
+                cbreak.c_lflag &= !libc::ECHO;
+            }
+            cbreak.c_cc[libc::VMIN] = 1;
+            cbreak.c_cc[libc::VTIME] = 0;
+            if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &cbreak) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self(saved))
+        }
+    }
+
+    impl Drop for Mode {
+        fn drop(&mut self) {
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.0) };
+        }
+    }
+}
+
+// keys are only read once enter is pressed
+#[cfg(not(unix))]
+mod terminal {
+    use std::io;
+
+    pub(super) struct Mode;
+
+    impl Mode {
+        pub(super) fn cbreak(_echo: bool) -> io::Result<Self> {
+            Ok(Self)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{key, Key, SharedInput, CTRL_C};
+    use crate::control::Control;
+    use std::{
+        io::{self, Write},
+        sync::{Arc, Mutex},
+    };
+
+    const CTRL_G: u8 = 0x07;
+
+    #[derive(Clone, Default)]
+    struct Stdin(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Stdin {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // a command that stopped reading its input
+    struct Closed;
+
+    impl Write for Closed {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn forwards_what_does_not_follow_the_escape_key() {
+        let (input, stdin) = (SharedInput::default(), Stdin::default());
+        let mut escaped = false;
+        assert!(!input.forward(b'a', &mut escaped));
+
+        input.set(Some(Box::new(stdin.clone())), CTRL_G);
+        let forwarded: Vec<bool> = [b'a', CTRL_G, b'r', CTRL_G, CTRL_G, CTRL_C, CTRL_G, CTRL_C]
+            .into_iter()
+            .map(|byte| input.forward(byte, &mut escaped))
+            .collect();
+
+        assert_eq!(
+            forwarded,
+            [true, true, false, true, true, false, true, true]
+        );
+        assert_eq!(*stdin.0.lock().unwrap(), [b'a', CTRL_G, CTRL_C]);
+        assert!(!escaped);
+    }
+
+    #[test]
+    fn gives_the_keys_back_once_the_command_stops_reading() {
+        let input = SharedInput::default();
+        input.set(Some(Box::new(Closed)), CTRL_G);
+        let mut escaped = false;
+
+        assert!(input.forward(b'a', &mut escaped));
+        assert_eq!(input.escape(), None);
+        assert!(!input.forward(b'r', &mut escaped));
+    }
+
+    #[test]
+    fn maps_the_keys_to_controls() {
+        let keys: Vec<Option<Key>> = [b'r', b'c', b'p', b'q', CTRL_C, b'?', b'x']
+            .into_iter()
+            .map(key)
+            .collect();
+
+        assert_eq!(
+            keys,
+            [
+                Some(Key::Control(Control::Restart)),
+                Some(Key::Control(Control::Clear)),
+                Some(Key::Control(Control::TogglePause)),
+                Some(Key::Control(Control::Quit)),
+                Some(Key::Control(Control::Interrupt)),
+                Some(Key::Help),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn interrupts_watchdog_unless_ctrl_c_follows_the_escape_key() {
+        let (input, stdin) = (SharedInput::default(), Stdin::default());
+        input.set(Some(Box::new(stdin.clone())), CTRL_G);
+        let mut escaped = false;
+        let keys: Vec<Option<Key>> = [CTRL_G, CTRL_C, CTRL_C]
+            .into_iter()
+            .filter(|&byte| !input.forward(byte, &mut escaped))
+            .map(key)
+            .collect();
+
+        assert_eq!(keys, [Some(Key::Control(Control::Interrupt))]);
+        assert_eq!(*stdin.0.lock().unwrap(), [CTRL_C]);
+    }
+}
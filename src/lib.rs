@@ -0,0 +1,31 @@
+#[cfg(feature = "async")]
+mod async_watch;
+mod check;
+pub mod cli;
+mod config;
+mod content_hash;
+mod control;
+mod event_source;
+mod init;
+mod keys;
+mod migrate;
+mod pause;
+mod reexec;
+mod rename;
+mod schema;
+mod signals;
+mod spawner;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod watch;
+
+#[cfg(feature = "async")]
+pub use async_watch::{AsyncWatchdog, Batch, Batches};
+pub use config::{
+    BoxedExtendable, Config, ConfigBuilder, ConfigChanges, Exclude, Exec, ExecPre, ExecPreCommand,
+    Extend, Extendable, ExtendableType, GitignoreSerde, Source, Sources, Watch, WatchEvent, When,
+};
+pub use control::{Control, Controller, Stopped};
+pub use event_source::{EventSource, NotifyEventSource};
+pub use spawner::{Process, ProcessSpawner, SystemSpawner};
+pub use watch::{Observer, Watchdog, WatchdogEvent};
@@ -1,12 +1,5 @@
-mod config;
-mod watch;
+use std::process;
 
-use config::Config;
-use notify::{Error, Result};
-use watch::watch;
-
-fn main() -> Result<()> {
-    let mut config = Config::new().map_err(|err| Error::generic(&format!("{err}")))?;
-
-    watch(&mut config)
+fn main() {
+    process::exit(watchdog::cli::run());
 }
@@ -0,0 +1,161 @@
+use crate::config::find_root;
+use std::fs;
+use toml_edit::{value, Array, DocumentMut, InlineTable, Item, Table, Value};
+
+// rewrites the deprecated [run]/[build] tables of watchdog.toml as [exec]/[exec_pre],
+// keeping everything else in the file, comments included
+pub fn migrate() -> i32 {
+    let config_path = match find_root() {
+        Ok(root) => root.join("watchdog.toml"),
+        Err(err) => {
+            eprintln!("{err}");
+            return 1;
+        }
+    };
+
+    let contents = match fs::read_to_string(&config_path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            eprintln!("Could not read {config_path:?}: {err}");
+            return 1;
+        }
+    };
+
+    let mut document = match contents.parse::<DocumentMut>() {
+        Ok(document) => document,
+        Err(err) => {
+            eprintln!("Could not parse {config_path:?}: {err}");
+            return 1;
+        }
+    };
+
+    if !document.contains_key("run") && !document.contains_key("build") {
+        println!("{config_path:?} doesn't use [run] or [build], nothing to migrate");
+        return 0;
+    }
+
+    if let Err(err) = migrate_document(&mut document) {
+        eprintln!("Could not migrate {config_path:?}: {err}");
+        return 1;
+    }
+
+    if let Err(err) = fs::write(&config_path, document.to_string()) {
+        eprintln!("Could not write {config_path:?}: {err}");
+        return 1;
+    }
+
+    println!("Migrated {config_path:?} to [exec] and [exec_pre]");
+    0
+}
+
+fn migrate_document(document: &mut DocumentMut) -> Result<(), String> {
+    let run = take_table(document, "run")?;
+    let build = take_table(document, "build")?;
+
+    if run.is_some() && document.contains_key("exec") {
+        return Err("both [run] and [exec] are set, merge them by hand".into());
+    }
+    if build.is_some() && document.contains_key("exec_pre") {
+        return Err("both [build] and [exec_pre] are set, merge them by hand".into());
+    }
+
+    let precompile = run
+        .as_ref()
+        .and_then(|run| run.get("precompile"))
+        .and_then(Item::as_bool)
+        .unwrap_or_default();
+
+    // the tables are reused rather than recreated so they keep their position and comments
+    if let Some(mut exec) = run {
+        exec.remove("precompile");
+        document.insert("exec", Item::Table(exec));
+    }
+
+    match (precompile, build) {
+        (true, None) => {
+            return Err("`precompile = true` under [run] requires a [build] table".into())
+        }
+        (true, Some(mut exec_pre)) => {
+            let mut command = InlineTable::new();
+            for key in ["command", "args"] {
+                if let Some(value) = exec_pre.remove(key).and_then(|item| item.into_value().ok()) {
+                    command.insert(key, value);
+                }
+            }
+            command.fmt();
+            let mut commands = Array::new();
+            commands.push(Value::InlineTable(command));
+
+            exec_pre.insert("when", value("always"));
+            exec_pre.insert("commands", value(commands));
+            document.insert("exec_pre", Item::Table(exec_pre));
+        }
+        // rather than dropping it, let the user decide whether it should run from now on
+        (false, Some(_)) => {
+            return Err(
+                "[build] was never run since `precompile = true` isn't set under [run], \
+                remove it or set `precompile = true` to run it before every [exec]"
+                    .into(),
+            )
+        }
+        (false, None) => {}
+    }
+
+    Ok(())
+}
+
+fn take_table(document: &mut DocumentMut, key: &str) -> Result<Option<Table>, String> {
+    match document.remove(key) {
+        Some(Item::Table(table)) => Ok(Some(table)),
+        Some(_) => Err(format!("[{key}] is expected to be a table")),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::migrate_document;
+    use toml_edit::DocumentMut;
+
+    fn migrate(contents: &str) -> Result<String, String> {
+        let mut document = contents.parse::<DocumentMut>().unwrap();
+        migrate_document(&mut document).map(|()| document.to_string())
+    }
+
+    #[test]
+    fn rewrites_run_and_build_keeping_the_rest() {
+        let migrated = migrate(
+            "# the app\n[run]\ncommand = \"cargo\"\nargs = [ \"run\" ]\nprecompile = true\n\n[build]\ncommand = \"cargo\"\nargs = [ \"build\" ]\norigin = \"app\"\n\n[exclude]\nfiles = [ { path = \"target\" } ]\n",
+        );
+
+        assert_eq!(
+            migrated.unwrap(),
+            "# the app\n[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n\n[exec_pre]\norigin = \"app\"\nwhen = \"always\"\ncommands = [{ command = \"cargo\", args = [ \"build\" ] }]\n\n[exclude]\nfiles = [ { path = \"target\" } ]\n"
+        );
+    }
+
+    #[test]
+    fn refuses_what_it_cannot_migrate_safely() {
+        let cases = [
+            (
+                "[run]\ncommand = \"cargo\"\n\n[build]\ncommand = \"make\"\n",
+                "[build] was never run",
+            ),
+            ("[build]\ncommand = \"make\"\n", "[build] was never run"),
+            (
+                "[run]\ncommand = \"cargo\"\nprecompile = true\n",
+                "requires a [build] table",
+            ),
+            (
+                "[run]\ncommand = \"cargo\"\n\n[exec]\ncommand = \"cargo\"\n",
+                "both [run] and [exec]",
+            ),
+            ("run = \"cargo\"\n", "[run] is expected to be a table"),
+        ];
+
+        for (contents, error) in cases {
+            let err = migrate(contents).unwrap_err();
+            assert!(err.contains(error), "{contents}: {err}");
+        }
+    }
+}
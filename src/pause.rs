@@ -0,0 +1,52 @@
+use crate::config::find_root;
+use std::fs;
+
+// watching is paused for as long as this file exists in the root project
+pub(crate) const PAUSE_FILE: &str = ".watchdog-pause";
+
+// pauses a watchdog running for the root project, by creating its pause file
+pub fn pause() -> i32 {
+    let pause_file = match find_root() {
+        Ok(root) => root.join(PAUSE_FILE),
+        Err(err) => {
+            eprintln!("{err}");
+            return 1;
+        }
+    };
+
+    match fs::write(&pause_file, "") {
+        Ok(()) => {
+            println!("created {pause_file:?}, watchdog is paused until it's removed");
+            0
+        }
+        Err(err) => {
+            eprintln!("Could not create {pause_file:?}: {err}");
+            1
+        }
+    }
+}
+
+pub fn resume() -> i32 {
+    let pause_file = match find_root() {
+        Ok(root) => root.join(PAUSE_FILE),
+        Err(err) => {
+            eprintln!("{err}");
+            return 1;
+        }
+    };
+
+    if !pause_file.exists() {
+        println!("{pause_file:?} doesn't exist, nothing to resume");
+        return 0;
+    }
+    match fs::remove_file(&pause_file) {
+        Ok(()) => {
+            println!("removed {pause_file:?}, watchdog is resumed");
+            0
+        }
+        Err(err) => {
+            eprintln!("Could not remove {pause_file:?}: {err}");
+            1
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+    time::SystemTime,
+};
+
+// set on every process watchdog spawns, so that a watchdog started by one of them
+// can tell it would be watching the very same project
+pub const WATCHDOG_ROOT: &str = "WATCHDOG_ROOT";
+const WATCHDOG_REEXEC: &str = "WATCHDOG_REEXEC";
+
+// the running watchdog binary, kept around so it can be re-executed once a rebuild replaced it
+#[derive(Debug)]
+pub struct SelfExe {
+    path: PathBuf,
+    dir: PathBuf,
+    args: Vec<OsString>,
+    modified: Option<SystemTime>,
+}
+
+impl SelfExe {
+    // the path has to be resolved before any rebuild happens: once the binary is replaced,
+    // the running process only points to the deleted file
+    pub fn new() -> io::Result<Self> {
+        let path = fs::canonicalize(env::current_exe()?)?;
+        Ok(Self::at(path, env::args_os().skip(1).collect()))
+    }
+
+    fn at(path: PathBuf, args: Vec<OsString>) -> Self {
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let modified = modified(&path);
+
+        Self {
+            path,
+            dir,
+            args,
+            modified,
+        }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    // build artifacts next to the binary change on every rebuild and would otherwise
+    // trigger a new run right after the re-exec
+    pub fn is_build_output(&self, path: &Path) -> bool {
+        path.starts_with(&self.dir)
+    }
+
+    pub fn was_rebuilt(&self) -> bool {
+        modified(&self.path) != self.modified
+    }
+
+    // only returns if the new binary couldn't be executed
+    pub fn reexec(&self) -> io::Error {
+        let mut command = Command::new(&self.path);
+        command.args(&self.args).env(WATCHDOG_REEXEC, "1");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            command.exec()
+        }
+
+        #[cfg(not(unix))]
+        {
+            match command.status() {
+                Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+                Err(err) => err,
+            }
+        }
+    }
+}
+
+// whether this process is a watchdog that re-executed itself after a rebuild
+pub fn take_reexec_marker() -> bool {
+    let reexec = env::var_os(WATCHDOG_REEXEC).is_some();
+    env::remove_var(WATCHDOG_REEXEC);
+    reexec
+}
+
+// spawning `cargo run` while watching watchdog's own project starts another watchdog on the same
+// root, which in turn spawns `cargo run` again and so on
+pub fn check_not_nested(root: &Path) -> Result<(), String> {
+    match env::var_os(WATCHDOG_ROOT) {
+        Some(parent_root) if Path::new(&parent_root) == root => Err(format!(
+            "Another watchdog is already watching {root:?} and started this one. \
+            Use `--reexec` (or `reexec = true` under [watch]) to have watchdog rebuild and restart itself instead."
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs::File, time::Duration};
+
+    #[test]
+    fn tells_when_the_binary_was_rebuilt() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("watchdog");
+        fs::write(&path, "old").unwrap();
+        let self_exe = SelfExe::at(path.clone(), Vec::new());
+        assert!(!self_exe.was_rebuilt());
+
+        let rebuilt = SystemTime::now() + Duration::from_secs(10);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(rebuilt)
+            .unwrap();
+        assert!(self_exe.was_rebuilt());
+
+        fs::remove_file(&path).unwrap();
+        assert!(self_exe.was_rebuilt());
+    }
+
+    #[test]
+    fn build_output_is_next_to_the_binary() {
+        let self_exe = SelfExe::at(PathBuf::from("/project/target/debug/watchdog"), Vec::new());
+
+        assert!(self_exe.is_build_output(Path::new("/project/target/debug/watchdog.d")));
+        assert!(self_exe.is_build_output(Path::new("/project/target/debug/deps/watchdog-1a2b")));
+        assert!(!self_exe.is_build_output(Path::new("/project/target/release/watchdog")));
+        assert!(!self_exe.is_build_output(Path::new("/project/src/main.rs")));
+    }
+
+    // both markers are process wide, so they're checked by a single test
+    #[test]
+    fn detects_nested_and_reexecuted_watchdogs() {
+        let root = Path::new("/project");
+        env::remove_var(WATCHDOG_ROOT);
+        assert!(check_not_nested(root).is_ok());
+        env::set_var(WATCHDOG_ROOT, "/other");
+        assert!(check_not_nested(root).is_ok());
+        env::set_var(WATCHDOG_ROOT, root);
+        assert!(check_not_nested(root).is_err());
+        env::remove_var(WATCHDOG_ROOT);
+
+        env::set_var(WATCHDOG_REEXEC, "1");
+        assert!(take_reexec_marker());
+        // not passed on to the commands the re-executed watchdog spawns
+        assert!(!take_reexec_marker());
+    }
+}
@@ -0,0 +1,277 @@
+use notify::{
+    event::{ModifyKind, RenameMode},
+    Event, EventKind,
+};
+use std::path::PathBuf;
+
+// editors usually save atomically by writing to a temporary file and renaming it over the real one,
+// which some backends report as separate rename-from/rename-to events; pair them back together so
+// the rename can be judged by where the file ended up rather than by the temporary file
+#[derive(Debug, Default)]
+pub struct Renames {
+    pending_from: Option<Event>,
+    last_paired: Option<usize>,
+}
+
+impl Renames {
+    pub fn pair(&mut self, event: Event) -> Vec<Event> {
+        let mut events = Vec::new();
+
+        match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                events.extend(self.flush());
+                self.pending_from = Some(event);
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => match self.pending_from.take() {
+                Some(from) if from.tracker() == event.tracker() => {
+                    self.last_paired = event.tracker();
+                    let mut both =
+                        Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+                            .add_some_path(from.paths.first().cloned())
+                            .add_some_path(event.paths.first().cloned());
+                    both.attrs = event.attrs;
+                    events.push(both);
+                }
+                // moved in from outside of the watched tree
+                from => {
+                    events.extend(from);
+                    events.push(event);
+                }
+            },
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                // inotify reports the paired rename again after the rename-to half
+                let already_paired =
+                    event.tracker().is_some() && self.last_paired == event.tracker();
+                self.last_paired = None;
+                if !already_paired {
+                    if event.tracker().is_some()
+                        && self.pending_from.as_ref().and_then(Event::tracker) == event.tracker()
+                    {
+                        self.pending_from = None;
+                    }
+                    events.extend(self.flush());
+                    events.push(event);
+                }
+            }
+            _ => {
+                events.extend(self.flush());
+                events.push(event);
+            }
+        }
+
+        events
+    }
+
+    #[cfg(feature = "async")]
+    pub fn is_pending(&self) -> bool {
+        self.pending_from.is_some()
+    }
+
+    // a rename-from still waiting for its rename-to once no more events came in, meaning the file
+    // was moved out of the watched tree; it's still reported as a rename, like a move into the tree
+    pub fn flush(&mut self) -> Option<Event> {
+        self.pending_from.take()
+    }
+}
+
+// renames are judged by their destination only, every other event by all of its paths
+pub fn judged_paths(event: &Event) -> &[PathBuf] {
+    match event.kind {
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() > 1 => {
+            &event.paths[event.paths.len() - 1..]
+        }
+        _ => &event.paths,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, content_hash::ContentHashes, watch::triggers_run};
+    use notify::event::{CreateKind, DataChange, RemoveKind};
+    use std::fs;
+    use tempfile::TempDir;
+
+    // a root project excluding the temporary files of vim and JetBrains editors
+    struct Project {
+        _dir: TempDir,
+        root: PathBuf,
+        config: Config,
+    }
+
+    impl Project {
+        fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let root = fs::canonicalize(dir.path()).unwrap();
+            fs::write(
+                root.join("watchdog.toml"),
+                "[exec]\ncommand = \"cargo\"\n\n[exclude]\nfiles = [ { path = \"4913\" }, { path = \"~\" }, { path = \"___jb_tmp___\" }, { path = \"___jb_old___\" } ]\n",
+            )
+            .unwrap();
+            let config = Config::builder().root(&root).build().unwrap();
+            Self {
+                _dir: dir,
+                root,
+                config,
+            }
+        }
+
+        fn path(&self, name: &str) -> PathBuf {
+            self.root.join(name)
+        }
+
+        fn event(&self, kind: EventKind, names: &[&str]) -> Event {
+            names.iter().fold(Event::new(kind), |event, name| {
+                event.add_path(self.path(name))
+            })
+        }
+
+        fn rename(&self, mode: RenameMode, names: &[&str], tracker: Option<usize>) -> Event {
+            let event = self.event(EventKind::Modify(ModifyKind::Name(mode)), names);
+            match tracker {
+                Some(tracker) => event.set_tracker(tracker),
+                None => event,
+            }
+        }
+
+        fn inotify_rename(&self, from: &str, to: &str, tracker: usize) -> Vec<Event> {
+            vec![
+                self.rename(RenameMode::From, &[from], Some(tracker)),
+                self.rename(RenameMode::To, &[to], Some(tracker)),
+                self.rename(RenameMode::Both, &[from, to], Some(tracker)),
+            ]
+        }
+
+        // mirrors the watch loop: the first paired event passing the config triggers a run,
+        // and a pending rename-from is flushed once no more events come
+        fn first_trigger(&self, events: Vec<Event>) -> Option<Event> {
+            let mut renames = Renames::default();
+            let mut content_hashes = ContentHashes::default();
+            let mut paired: Vec<Event> = events
+                .into_iter()
+                .flat_map(|event| renames.pair(event))
+                .collect();
+            paired.extend(renames.flush());
+            paired
+                .into_iter()
+                .find(|event| triggers_run(&self.config, event, None, &mut content_hashes))
+        }
+    }
+
+    #[test]
+    fn vim_backup_save_triggers_on_recreated_file() {
+        let project = Project::new();
+        let mut events = vec![
+            project.event(EventKind::Create(CreateKind::File), &["4913"]),
+            project.event(EventKind::Remove(RemoveKind::File), &["4913"]),
+        ];
+        events.extend(project.inotify_rename("main.rs", "main.rs~", 1));
+        events.push(project.event(EventKind::Create(CreateKind::File), &["main.rs"]));
+        events.push(project.event(EventKind::Remove(RemoveKind::File), &["main.rs~"]));
+
+        let trigger = project.first_trigger(events).unwrap();
+        assert_eq!(trigger.kind, EventKind::Create(CreateKind::File));
+        assert_eq!(trigger.paths, vec![project.path("main.rs")]);
+    }
+
+    #[test]
+    fn jetbrains_safe_write_triggers_on_rename_to_watched_file() {
+        let project = Project::new();
+        let mut events = vec![
+            project.event(
+                EventKind::Create(CreateKind::File),
+                &["main.rs___jb_tmp___"],
+            ),
+            project.event(
+                EventKind::Modify(ModifyKind::Data(DataChange::Any)),
+                &["main.rs___jb_tmp___"],
+            ),
+        ];
+        events.extend(project.inotify_rename("main.rs", "main.rs___jb_old___", 1));
+        events.extend(project.inotify_rename("main.rs___jb_tmp___", "main.rs", 2));
+        events.push(project.event(
+            EventKind::Remove(RemoveKind::File),
+            &["main.rs___jb_old___"],
+        ));
+
+        let trigger = project.first_trigger(events).unwrap();
+        assert_eq!(
+            trigger.kind,
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both))
+        );
+        assert_eq!(
+            trigger.paths,
+            vec![project.path("main.rs___jb_tmp___"), project.path("main.rs")]
+        );
+    }
+
+    #[test]
+    fn rename_to_ignored_file_is_ignored() {
+        let project = Project::new();
+        let events = vec![project.rename(RenameMode::Both, &["main.rs", "main.rs~"], Some(1))];
+
+        assert!(project.first_trigger(events).is_none());
+    }
+
+    #[test]
+    fn untracked_rename_halves_are_paired() {
+        let project = Project::new();
+        let events = vec![
+            project.rename(RenameMode::From, &["main.rs___jb_tmp___"], None),
+            project.rename(RenameMode::To, &["main.rs"], None),
+        ];
+
+        let mut renames = Renames::default();
+        let paired: Vec<Event> = events
+            .into_iter()
+            .flat_map(|event| renames.pair(event))
+            .collect();
+        assert_eq!(paired.len(), 1);
+        assert_eq!(judged_paths(&paired[0]), [project.path("main.rs")]);
+    }
+
+    #[test]
+    fn inotify_rename_is_reported_once() {
+        let project = Project::new();
+        let mut renames = Renames::default();
+        let paired: Vec<Event> = project
+            .inotify_rename("a.rs", "b.rs", 1)
+            .into_iter()
+            .flat_map(|event| renames.pair(event))
+            .collect();
+
+        assert_eq!(paired.len(), 1);
+        assert_eq!(
+            paired[0].paths,
+            vec![project.path("a.rs"), project.path("b.rs")]
+        );
+    }
+
+    #[test]
+    fn rename_out_of_tree_is_a_rename() {
+        let project = Project::new();
+        let events = vec![
+            project.rename(RenameMode::From, &["main.rs"], Some(1)),
+            project.event(EventKind::Create(CreateKind::File), &["4913"]),
+        ];
+
+        let trigger = project.first_trigger(events).unwrap();
+        assert_eq!(
+            trigger.kind,
+            EventKind::Modify(ModifyKind::Name(RenameMode::From))
+        );
+        assert_eq!(trigger.paths, vec![project.path("main.rs")]);
+    }
+
+    #[test]
+    fn lone_rename_out_of_tree_is_flushed() {
+        let project = Project::new();
+        let events = vec![project.rename(RenameMode::From, &["main.rs"], Some(1))];
+
+        let trigger = project.first_trigger(events).unwrap();
+        assert_eq!(
+            trigger.kind,
+            EventKind::Modify(ModifyKind::Name(RenameMode::From))
+        );
+    }
+}
@@ -0,0 +1,20 @@
+use crate::config::FileOpt;
+
+pub fn schema() -> i32 {
+    println!("{}", FileOpt::schema());
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::FileOpt;
+
+    // the published schema is generated from the config types, regenerate it whenever they change
+    #[test]
+    fn schema_is_up_to_date() {
+        assert!(
+            FileOpt::schema().trim_end() == include_str!("../watchdog.schema.json").trim_end(),
+            "watchdog.schema.json is out of date, run `cargo run -- schema > watchdog.schema.json`"
+        );
+    }
+}
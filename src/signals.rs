@@ -0,0 +1,52 @@
+use crate::control::Controller;
+use std::io;
+
+// forwards the signals sent to watchdog as controls, until dropped
+pub(crate) struct SignalControls {
+    #[cfg(unix)]
+    handle: signal_hook::iterator::Handle,
+}
+
+#[cfg(unix)]
+impl SignalControls {
+    pub(crate) fn start(controller: Controller) -> io::Result<Self> {
+        use crate::control::Control;
+        use signal_hook::{
+            consts::{SIGHUP, SIGINT, SIGTERM, SIGUSR1},
+            iterator::Signals,
+        };
+
+        let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP, SIGUSR1])?;
+        let handle = signals.handle();
+        std::thread::spawn(move || {
+            for signal in signals.forever() {
+                let control = match signal {
+                    SIGINT => Control::Interrupt,
+                    SIGTERM => Control::Terminate,
+                    SIGHUP => Control::ReloadConfig,
+                    SIGUSR1 => Control::TogglePause,
+                    _ => continue,
+                };
+                if !controller.send(control) {
+                    break;
+                }
+            }
+        });
+        Ok(Self { handle })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for SignalControls {
+    fn drop(&mut self) {
+        // the signals are unregistered once the thread is done with them
+        self.handle.close();
+    }
+}
+
+#[cfg(not(unix))]
+impl SignalControls {
+    pub(crate) fn start(_controller: Controller) -> io::Result<Self> {
+        Ok(Self {})
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    io::{self, Write},
+    process::{Child, Command, ExitStatus},
+    thread,
+    time::{Duration, Instant},
+};
+
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+// how a Watchdog starts its commands, as processes unless running tests
+pub trait ProcessSpawner: Send {
+    fn spawn(&mut self, command: &mut Command) -> io::Result<Box<dyn Process>>;
+}
+
+pub trait Process: Send {
+    fn kill(&mut self) -> io::Result<()>;
+
+    // asks the process to stop, giving it a chance to clean up unlike `kill`
+    fn terminate(&mut self) -> io::Result<()> {
+        self.kill()
+    }
+
+    fn wait(&mut self) -> io::Result<ExitStatus>;
+
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>>;
+
+    // `None` if the process is still running after the timeout
+    fn wait_timeout(&mut self, timeout: Duration) -> io::Result<Option<ExitStatus>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = self.try_wait()? {
+                return Ok(Some(status));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            thread::sleep(WAIT_POLL_INTERVAL.min(deadline - now));
+        }
+    }
+
+    // the input of a process spawned with a piped stdin
+    fn take_stdin(&mut self) -> Option<Box<dyn Write + Send>> {
+        None
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SystemSpawner;
+
+impl ProcessSpawner for SystemSpawner {
+    fn spawn(&mut self, command: &mut Command) -> io::Result<Box<dyn Process>> {
+        Ok(Box::new(command.spawn()?))
+    }
+}
+
+impl Process for Child {
+    fn kill(&mut self) -> io::Result<()> {
+        Child::kill(self)
+    }
+
+    #[cfg(unix)]
+    fn terminate(&mut self) -> io::Result<()> {
+        // it may have exited already, then there's nothing to terminate
+        if Child::try_wait(self)?.is_some() {
+            return Ok(());
+        }
+        let pid = libc::pid_t::try_from(self.id()).map_err(io::Error::other)?;
+        match unsafe { libc::kill(pid, libc::SIGTERM) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    fn wait(&mut self) -> io::Result<ExitStatus> {
+        Child::wait(self)
+    }
+
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        Child::try_wait(self)
+    }
+
+    fn take_stdin(&mut self) -> Option<Box<dyn Write + Send>> {
+        self.stdin
+            .take()
+            .map(|stdin| Box::new(stdin) as Box<dyn Write + Send>)
+    }
+}
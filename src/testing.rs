@@ -0,0 +1,261 @@
+// fakes to run a Watchdog deterministically in tests, without watching files nor starting processes
+use crate::{
+    event_source::EventSource,
+    spawner::{Process, ProcessSpawner},
+    watch::{Observer, WatchdogEvent},
+};
+use notify::{
+    event::{CreateKind, DataChange, ModifyKind, RemoveKind, RenameMode},
+    Event, EventKind, RecursiveMode, Result,
+};
+use std::{
+    collections::VecDeque,
+    io,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
+    sync::{mpsc::RecvTimeoutError, Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+pub fn create(path: impl Into<PathBuf>) -> Event {
+    Event::new(EventKind::Create(CreateKind::File)).add_path(path.into())
+}
+
+pub fn modify(path: impl Into<PathBuf>) -> Event {
+    Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content))).add_path(path.into())
+}
+
+pub fn remove(path: impl Into<PathBuf>) -> Event {
+    Event::new(EventKind::Remove(RemoveKind::File)).add_path(path.into())
+}
+
+// the first half of a rename that no second half follows, e.g when moving a file out of the root
+pub fn rename_from(from: impl Into<PathBuf>) -> Event {
+    Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::From))).add_path(from.into())
+}
+
+pub fn rename(from: impl Into<PathBuf>, to: impl Into<PathBuf>) -> Event {
+    Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+        .add_path(from.into())
+        .add_path(to.into())
+}
+
+// hands out scripted batches of events, each followed by a timeout as if no more events came in
+// for a while, then disconnects so that `Watchdog::run` returns; the events of a batch that come
+// after the one triggering a run are drained, as if they happened within the debounce time
+#[derive(Debug, Clone, Default)]
+pub struct FakeEventSource {
+    state: Arc<Mutex<FakeEventSourceState>>,
+}
+
+#[derive(Debug, Default)]
+struct FakeEventSourceState {
+    batches: VecDeque<VecDeque<Event>>,
+    watched: Vec<(PathBuf, RecursiveMode)>,
+}
+
+impl FakeEventSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn batch(self, events: impl IntoIterator<Item = Event>) -> Self {
+        self.lock().batches.push_back(events.into_iter().collect());
+        self
+    }
+
+    pub fn watched(&self) -> Vec<(PathBuf, RecursiveMode)> {
+        self.lock().watched.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, FakeEventSourceState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl EventSource for FakeEventSource {
+    fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> Result<()> {
+        self.lock()
+            .watched
+            .push((path.to_path_buf(), recursive_mode));
+        Ok(())
+    }
+
+    fn recv_timeout(
+        &mut self,
+        _timeout: Duration,
+    ) -> std::result::Result<Result<Event>, RecvTimeoutError> {
+        let mut state = self.lock();
+        let batch = state
+            .batches
+            .front_mut()
+            .ok_or(RecvTimeoutError::Disconnected)?;
+        if let Some(event) = batch.pop_front() {
+            return Ok(Ok(event));
+        }
+        state.batches.pop_front();
+        Err(RecvTimeoutError::Timeout)
+    }
+
+    fn drain(&mut self) -> Vec<Event> {
+        self.lock()
+            .batches
+            .pop_front()
+            .map(Vec::from)
+            .unwrap_or_default()
+    }
+}
+
+// records the commands instead of running them; they keep running until killed, except when
+// waited for, e.g exec_pre commands, which succeed right away unless killed meanwhile
+#[derive(Debug, Clone, Default)]
+pub struct FakeSpawner {
+    spawned: Arc<Mutex<Vec<SpawnedCommand>>>,
+    failing: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpawnedCommand {
+    program: String,
+    args: Vec<String>,
+    current_dir: Option<PathBuf>,
+}
+
+impl SpawnedCommand {
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    pub fn current_dir(&self) -> Option<&Path> {
+        self.current_dir.as_deref()
+    }
+
+    // e.g "cargo build", to compare against in tests
+    pub fn command_line(&self) -> String {
+        let mut command_line = self.program.clone();
+        for arg in &self.args {
+            command_line.push(' ');
+            command_line.push_str(arg);
+        }
+        command_line
+    }
+}
+
+impl FakeSpawner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // programs that can't be spawned, as if they weren't installed
+    pub fn failing(mut self, program: impl Into<String>) -> Self {
+        self.failing.push(program.into());
+        self
+    }
+
+    pub fn spawned(&self) -> Vec<SpawnedCommand> {
+        self.spawned
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    pub fn command_lines(&self) -> Vec<String> {
+        self.spawned()
+            .iter()
+            .map(SpawnedCommand::command_line)
+            .collect()
+    }
+}
+
+impl ProcessSpawner for FakeSpawner {
+    fn spawn(&mut self, command: &mut Command) -> io::Result<Box<dyn Process>> {
+        let program = command.get_program().to_string_lossy();
+        if self.failing.iter().any(|failing| *failing == program) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{program} not found"),
+            ));
+        }
+        self.spawned
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(SpawnedCommand {
+                program: command.get_program().to_string_lossy().into_owned(),
+                args: command
+                    .get_args()
+                    .map(|arg| arg.to_string_lossy().into_owned())
+                    .collect(),
+                current_dir: command.get_current_dir().map(Path::to_path_buf),
+            });
+        Ok(Box::new(FakeProcess::default()))
+    }
+}
+
+#[derive(Debug, Default)]
+struct FakeProcess {
+    killed: bool,
+}
+
+impl Process for FakeProcess {
+    fn kill(&mut self) -> io::Result<()> {
+        self.killed = true;
+        Ok(())
+    }
+
+    fn wait(&mut self) -> io::Result<ExitStatus> {
+        Ok(exit_status(self.killed))
+    }
+
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        Ok(self.killed.then(|| exit_status(true)))
+    }
+
+    fn wait_timeout(&mut self, _timeout: Duration) -> io::Result<Option<ExitStatus>> {
+        Ok(Some(exit_status(self.killed)))
+    }
+}
+
+#[cfg(unix)]
+fn exit_status(killed: bool) -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+    // the raw wait status of a process killed by SIGKILL
+    ExitStatus::from_raw(if killed { 9 } else { 0 })
+}
+
+#[cfg(windows)]
+fn exit_status(killed: bool) -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+    ExitStatus::from_raw(if killed { 1 } else { 0 })
+}
+
+// keeps the events of a Watchdog, to assert on them once it's done
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    events: Arc<Mutex<Vec<WatchdogEvent>>>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<WatchdogEvent> {
+        self.events
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+}
+
+impl Observer for Recorder {
+    fn notify(&mut self, event: &WatchdogEvent) {
+        self.events
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(event.clone());
+    }
+}
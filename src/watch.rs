@@ -1,149 +1,809 @@
-use crate::config::{Config, ExecPre, Extendable, When};
+use crate::{
+    config::{Config, When},
+    content_hash::ContentHashes,
+    control::{Control, Controller, Stopped},
+    event_source::{EventSource, NotifyEventSource},
+    keys::{Keys, SharedInput},
+    pause::PAUSE_FILE,
+    reexec::{check_not_nested, take_reexec_marker, SelfExe, WATCHDOG_ROOT},
+    rename::{judged_paths, Renames},
+    signals::SignalControls,
+    spawner::{Process, ProcessSpawner, SystemSpawner},
+};
 use notify::{
-    event::{CreateKind, RemoveKind},
-    Config as NotifyConfig, Event, EventKind, RecommendedWatcher, RecursiveMode, Result, Watcher,
+    event::{CreateKind, ModifyKind, RemoveKind},
+    Error, Event, EventKind, RecursiveMode, Result,
 };
 use std::{
-    path::PathBuf,
-    process::{Child, Command},
-    sync::mpsc::channel,
+    collections::VecDeque,
+    mem,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-pub fn watch(config: &mut Config) -> Result<()> {
-    let mut child_proc = exec(config, false);
+// what a running watchdog reports to its observers, more kinds may be added in minor releases
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WatchdogEvent {
+    // the paths of the file event that triggered a run or a config reload
+    BatchReceived {
+        paths: Vec<PathBuf>,
+    },
+    CommandStarted {
+        command: String,
+        args: Vec<String>,
+        pre: bool,
+    },
+    // `status` is `None` when the command couldn't be waited for
+    CommandExited {
+        command: String,
+        status: Option<ExitStatus>,
+        pre: bool,
+    },
+    ConfigReloaded {
+        restart: bool,
+    },
+    Paused,
+    // the paths that changed while paused
+    Resumed {
+        paths: Vec<PathBuf>,
+    },
+}
 
-    let (tx, rx) = channel();
-    let notify_config = NotifyConfig::default();
-    let mut watcher =
-        RecommendedWatcher::new(tx, NotifyConfig::with_compare_contents(notify_config, true))?;
+pub trait Observer: Send {
+    fn notify(&mut self, event: &WatchdogEvent);
+}
 
-    let root = config.root();
-    watcher.watch(root, RecursiveMode::Recursive)?;
+impl<F: FnMut(&WatchdogEvent) + Send> Observer for F {
+    fn notify(&mut self, event: &WatchdogEvent) {
+        self(event)
+    }
+}
 
-    loop {
-        // there is an assumption here that when performing an operation such as creating/removing
-        // or saving a file, the first event will always contain the path we're interested in
-        let res = rx.recv();
-        match res {
-            Ok(Ok(event)) => event_handler(event, config, &mut child_proc),
-            err => eprintln!("Watch error: {err:?}"),
+// polled while waiting for file events, so that observers learn about the command exiting
+const CHILD_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// how long a command has to exit once asked to when stopping, before it's killed
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Watchdog {
+    config: Config,
+    observers: Vec<Box<dyn Observer>>,
+    event_source: Option<Box<dyn EventSource>>,
+    spawner: Box<dyn ProcessSpawner>,
+    child: Option<RunningCommand>,
+    content_hashes: ContentHashes,
+    self_exe: Option<SelfExe>,
+    controller: Controller,
+    controls: Controls,
+    read_keys: bool,
+    keys: Option<Keys>,
+    input: Option<SharedInput>,
+    handle_signals: bool,
+    signals: Option<SignalControls>,
+    paused: bool,
+    missed: Vec<PathBuf>,
+}
+
+struct RunningCommand {
+    command: String,
+    child: Box<dyn Process>,
+}
+
+impl Watchdog {
+    pub fn new(config: Config) -> Self {
+        let (tx, controls) = channel();
+        Self {
+            config,
+            observers: Vec::new(),
+            event_source: None,
+            spawner: Box::new(SystemSpawner),
+            child: None,
+            content_hashes: ContentHashes::default(),
+            self_exe: None,
+            controller: Controller::new(tx),
+            controls: Controls {
+                rx: controls,
+                deferred: VecDeque::new(),
+                stopping: None,
+            },
+            read_keys: false,
+            keys: None,
+            input: None,
+            handle_signals: false,
+            signals: None,
+            paused: false,
+            missed: Vec::new(),
         }
+    }
 
-        // there's usually more events happening in the background e.g. git updating some internal files
-        // there's no need to react on those events since the process has just been reloaded
-        // so discard all of them; it's highly unlikely to make two changes in under half a second
-        {
-            thread::sleep(Duration::from_millis(500));
-            while rx.try_recv().is_ok() {}
+    pub fn observe(mut self, observer: impl Observer + 'static) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    // the file system is watched unless another source of events is given, e.g in tests
+    pub fn event_source(mut self, event_source: impl EventSource + 'static) -> Self {
+        self.event_source = Some(Box::new(event_source));
+        self
+    }
+
+    pub fn spawner(mut self, spawner: impl ProcessSpawner + 'static) -> Self {
+        self.spawner = Box::new(spawner);
+        self
+    }
+
+    // reads the keys typed in the terminal, e.g r to restart or q to quit, unless [watch] keys is
+    // off or stdin isn't a terminal
+    pub fn keys(mut self) -> Self {
+        self.read_keys = true;
+        self
+    }
+
+    // reacts to the signals sent to the process, SIGUSR1 pausing or resuming watching
+    pub fn signals(mut self) -> Self {
+        self.handle_signals = true;
+        self
+    }
+
+    pub fn controller(&self) -> Controller {
+        self.controller.clone()
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    // runs the command and restarts it on every change, only returns if watching fails or when
+    // asked to stop
+    pub fn run(mut self) -> Result<Stopped> {
+        check_not_nested(self.config.root()).map_err(|err| Error::generic(&err))?;
+
+        self.self_exe = SelfExe::new()
+            .inspect_err(|err| eprintln!("Could not locate the watchdog executable: {err}"))
+            .ok();
+        if self.read_keys && self.config.to_watch().keys() {
+            let input = SharedInput::default();
+            let echo = self.config.to_exec().interactive();
+            self.keys = Keys::start(self.controller.clone(), input.clone(), echo);
+            self.input = self.keys.as_ref().map(|_| input);
+        }
+        if self.handle_signals {
+            self.signals = SignalControls::start(self.controller.clone())
+                .inspect_err(|err| eprintln!("Could not handle signals: {err}"))
+                .ok();
+        }
+        self.exec(take_reexec_marker());
+        if pause_file(&self.config).exists() {
+            self.set_paused(true);
+        }
+        self.content_hashes = ContentHashes::new(&self.config);
+        let mut renames = Renames::default();
+
+        let mut event_source = match self.event_source.take() {
+            Some(event_source) => event_source,
+            None => Box::new(NotifyEventSource::new()?),
+        };
+        event_source.watch(self.config.root(), RecursiveMode::Recursive)?;
+        watch_config_dirs(&self.config, |dir| {
+            event_source.watch(dir, RecursiveMode::NonRecursive)
+        });
+
+        loop {
+            while self.controls.stopping.is_none() {
+                let Some(control) = self.controls.next() else {
+                    break;
+                };
+                match control.stops() {
+                    Some(stopped) => self.controls.stopping = Some(stopped),
+                    None => self.control(control),
+                }
+            }
+            if let Some(stopped) = self.controls.stopping {
+                self.quit();
+                return Ok(stopped);
+            }
+
+            // saving a file can take several events (e.g. atomic saves writing to a temporary file
+            // and renaming it), so keep going through them until one of them actually triggers a run
+            let events = match event_source.recv_timeout(CHILD_POLL_INTERVAL) {
+                Ok(Ok(event)) => renames.pair(event),
+                Ok(Err(err)) => {
+                    eprintln!("Watch error: {err:?}");
+                    Vec::new()
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    self.poll_child();
+                    // a rename-from that no rename-to followed, the file was moved out of the tree
+                    renames.flush().into_iter().collect()
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(Error::generic("the file watcher stopped"))
+                }
+            };
+            let mut triggered = false;
+            for event in events {
+                triggered |= self.event_handler(event);
+            }
+
+            // there's usually more events happening in the background e.g. git updating some internal files
+            // there's no need to react on those events since the process has just been reloaded
+            // so discard all of them; it's highly unlikely to make two changes within the debounce time
+            if triggered {
+                thread::sleep(self.config.to_watch().debounce());
+                // the drained changes don't restart the command, but later ones are compared
+                // against them
+                let mut reload_config = false;
+                for event in event_source.drain() {
+                    self.content_hashes.refresh(&self.config, &event);
+                    reload_config |= self.drained_event(event);
+                }
+                if reload_config {
+                    self.reload_config();
+                }
+                renames = Renames::default();
+                // a reloaded config may extend files that weren't watched so far
+                watch_config_dirs(&self.config, |dir| {
+                    event_source.watch(dir, RecursiveMode::NonRecursive)
+                });
+            }
         }
     }
-}
 
-fn exec(config: &Config, restart: bool) -> Option<Child> {
-    let _ = Command::new("clear").spawn().unwrap().wait();
+    fn emit(&mut self, event: WatchdogEvent) {
+        for observer in &mut self.observers {
+            observer.notify(&event);
+        }
+    }
+
+    fn control(&mut self, control: Control) {
+        match control {
+            Control::Restart => {
+                self.stop();
+                self.exec(true);
+            }
+            Control::Clear => self.clear(),
+            Control::Pause => {
+                self.set_paused(true);
+            }
+            Control::Resume => {
+                self.set_paused(false);
+            }
+            Control::TogglePause => {
+                self.set_paused(!self.paused);
+            }
+            Control::ReloadConfig => self.reload_config(),
+            // the loop stops the command before returning
+            Control::Quit | Control::Interrupt | Control::Terminate => {}
+        }
+    }
+
+    // true if resuming triggered a run
+    fn set_paused(&mut self, paused: bool) -> bool {
+        if paused == self.paused {
+            return false;
+        }
+        self.paused = paused;
+        if paused {
+            println!("paused, changes are collected until resumed");
+            self.emit(WatchdogEvent::Paused);
+            return false;
+        }
+
+        let paths = mem::take(&mut self.missed);
+        self.emit(WatchdogEvent::Resumed {
+            paths: paths.clone(),
+        });
+        if paths.is_empty() {
+            println!("resumed");
+            return false;
+        }
+        println!("resumed, changed while paused: {paths:?}");
 
-    if let Some(exec_pre) = config.to_exec_pre() {
-        match exec_pre.when() {
-            When::Once if !restart => execute_pre(exec_pre),
-            When::Always => execute_pre(exec_pre),
-            _ => {}
+        let mut event = Event::new(EventKind::Any);
+        event.paths = paths;
+        // a changed config is reloaded either way, rather than being left stale
+        if !self.config.to_watch().run_on_resume() && !should_reload_config(&self.config, &event) {
+            return false;
         }
+        self.handler(event);
+        true
     }
 
-    let exec = config.to_exec();
-    let command = exec.command();
-    let args = exec.args();
-    let origin = exec.origin();
-    println!(
-        "executing command {:?} with args {:?} at origin {:?}",
-        command, args, origin
-    );
-    Command::new(command)
-        .args(args)
-        .current_dir(origin)
-        .spawn()
-        .ok()
-}
+    fn clear(&mut self) {
+        let _ = self
+            .spawner
+            .spawn(&mut Command::new("clear"))
+            .and_then(|mut clear| clear.wait());
+    }
+
+    fn exec(&mut self, restart: bool) {
+        if self.config.to_watch().clear() {
+            self.clear();
+        }
+        let config = &self.config;
+        if !restart {
+            print_banner(config);
+            if self.keys.is_some() {
+                println!("  keys: press ? for help");
+            }
+        }
+
+        if let Some(exec_pre) = config.to_exec_pre() {
+            match exec_pre.when() {
+                When::Once if !restart => self.execute_pre(),
+                When::Always => self.execute_pre(),
+                _ => {}
+            }
+        }
+        if self.controls.stopping.is_some() {
+            return;
+        }
+
+        // exec-pre just rebuilt watchdog itself, so hand over to the new binary
+        let config = &self.config;
+        if let Some(self_exe) = self
+            .self_exe
+            .as_ref()
+            .filter(|self_exe| config.to_watch().reexec() && self_exe.was_rebuilt())
+        {
+            println!("re-executing rebuilt watchdog {:?}", self_exe.path());
+            // the new watchdog sets up the terminal again, from how it was before this one did
+            drop(self.keys.take());
+            let err = self_exe.reexec();
+            eprintln!("Could not re-execute {:?}: {err}", self_exe.path());
+        }
 
-fn execute_pre(exec_pre: &ExecPre) {
-    let commands = exec_pre.commands();
-    commands.iter().for_each(|exec_pre_command| {
-        let command = exec_pre_command.command();
-        let args = exec_pre_command.args();
-        let origin = exec_pre.origin();
+        let exec = config.to_exec();
+        let command = exec.command();
+        let args = exec.args();
+        let origin = exec.origin();
         println!(
-            "pre executing command {:?} with args {:?} at origin {:?}",
+            "executing command {:?} with args {:?} at origin {:?}",
             command, args, origin
         );
-        let _ = Command::new(command)
-            .args(args)
-            .current_dir(origin)
-            .spawn()
-            .map_err(|err| {
-                format!(
-                    "Something went wrong when executing command {:?} with args {:?}. {:?}",
-                    command, args, err
-                )
-            })
-            .unwrap()
-            .wait();
-    });
+        let mut child_command = self::command(command, args, origin, config.root());
+        // the terminal's input is for the keys, unless the command is interactive
+        if self.input.is_some() {
+            child_command.stdin(match exec.interactive() {
+                true => Stdio::piped(),
+                false => Stdio::null(),
+            });
+        }
+        let mut child = self.spawner.spawn(&mut child_command).ok();
+        if let Some(input) = &self.input {
+            let stdin = child.as_mut().and_then(|child| child.take_stdin());
+            input.set(stdin, config.to_watch().escape_byte());
+        }
+
+        if let Some(child) = child {
+            let (command, args) = (command.to_owned(), args.to_vec());
+            self.child = Some(RunningCommand {
+                command: command.clone(),
+                child,
+            });
+            self.emit(WatchdogEvent::CommandStarted {
+                command,
+                args,
+                pre: false,
+            });
+        }
+    }
+
+    fn execute_pre(&mut self) {
+        let Some(exec_pre) = self.config.to_exec_pre() else {
+            return;
+        };
+        // taken out of the config so that the events can be emitted while the commands run
+        let origin = exec_pre.origin().clone();
+        let commands: Vec<(String, Vec<String>)> = exec_pre
+            .commands()
+            .iter()
+            .map(|command| (command.command().to_owned(), command.args().to_vec()))
+            .collect();
+
+        for (command, args) in commands {
+            println!(
+                "pre executing command {:?} with args {:?} at origin {:?}",
+                command, args, origin
+            );
+            let mut child_command = self::command(&command, &args, &origin, self.config.root());
+            if self.input.is_some() {
+                child_command.stdin(Stdio::null());
+            }
+            let mut child = match self.spawner.spawn(&mut child_command) {
+                Ok(child) => child,
+                Err(err) => {
+                    eprintln!(
+                        "Something went wrong when executing command {command:?} with args {args:?}, skipping it. {err}"
+                    );
+                    continue;
+                }
+            };
+            self.emit(WatchdogEvent::CommandStarted {
+                command: command.clone(),
+                args,
+                pre: true,
+            });
+            let status = wait_pre(child.as_mut(), &mut self.controls);
+            self.emit(WatchdogEvent::CommandExited {
+                command,
+                status,
+                pre: true,
+            });
+            if self.controls.stopping.is_some() {
+                break;
+            }
+        }
+    }
+
+    fn handler(&mut self, event: Event) {
+        self.emit(WatchdogEvent::BatchReceived {
+            paths: event.paths.clone(),
+        });
+
+        if should_reload_config(&self.config, &event) {
+            return self.reload_config();
+        }
+
+        self.stop();
+        self.exec(true);
+    }
+
+    fn reload_config(&mut self) {
+        let new_config = match self.config.reload() {
+            Ok(new_config) => new_config,
+            Err(err) => {
+                eprintln!("Error loading new config, keeping the previous one:\n{err}");
+                return;
+            }
+        };
+
+        let changes = self.config.changes(&new_config);
+        self.config = new_config;
+        if self.config.to_watch().hash_contents() {
+            self.content_hashes.prefill(&self.config);
+        }
+
+        self.emit(WatchdogEvent::ConfigReloaded {
+            restart: changes.requires_restart(),
+        });
+        if !changes.requires_restart() {
+            println!("watchdog.toml reloaded, nothing to restart");
+            return;
+        }
+
+        self.stop();
+        // a changed exec_pre is run as if watchdog just started, including `once` commands
+        self.exec(!changes.exec_pre());
+    }
+
+    fn stop(&mut self) {
+        if let Some(mut running) = self.child.take() {
+            let _ = running.child.kill();
+            let status = running.child.wait().ok();
+            self.emit(WatchdogEvent::CommandExited {
+                command: running.command,
+                status,
+                pre: false,
+            });
+        }
+    }
+
+    // unlike `stop`, the command is given the time to clean up before being killed
+    fn quit(&mut self) {
+        if let Some(mut running) = self.child.take() {
+            let status = stop_gracefully(running.child.as_mut(), &mut self.controls);
+            self.emit(WatchdogEvent::CommandExited {
+                command: running.command,
+                status,
+                pre: false,
+            });
+        }
+    }
+
+    fn poll_child(&mut self) {
+        let Some(running) = self.child.as_mut() else {
+            return;
+        };
+        if let Ok(Some(status)) = running.child.try_wait() {
+            let running = self.child.take().unwrap();
+            self.emit(WatchdogEvent::CommandExited {
+                command: running.command,
+                status: Some(status),
+                pre: false,
+            });
+        }
+    }
+
+    fn event_handler(&mut self, event: Event) -> bool {
+        let pause_file = pause_file(&self.config);
+        if event.paths.contains(&pause_file) {
+            return self.set_paused(pause_file.exists());
+        }
+
+        if !triggers_run(
+            &self.config,
+            &event,
+            self.self_exe.as_ref(),
+            &mut self.content_hashes,
+        ) {
+            return false;
+        }
+        if self.paused {
+            self.miss(event.paths);
+            return false;
+        }
+
+        self.handler(event);
+        true
+    }
+
+    // a change drained after a run still pauses or resumes watchdog, true if it's to a config
+    // file, which is reloaded once the drained changes are gone through
+    fn drained_event(&mut self, event: Event) -> bool {
+        let pause_file = pause_file(&self.config);
+        if event.paths.contains(&pause_file) {
+            self.set_paused(pause_file.exists());
+            return false;
+        }
+
+        if !should_reload_config(&self.config, &event) || matches!(event.kind, EventKind::Access(_))
+        {
+            return false;
+        }
+        if self.paused {
+            self.miss(event.paths);
+            return false;
+        }
+        true
+    }
+
+    fn miss(&mut self, paths: Vec<PathBuf>) {
+        for path in paths {
+            if !self.missed.contains(&path) {
+                self.missed.push(path);
+            }
+        }
+    }
+}
+
+// however `run` returns, e.g when the file watcher fails, the command doesn't outlive watchdog
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.quit();
+    }
+}
+
+// the controls sent to a Watchdog, some of them kept for later while it's busy running exec_pre
+// commands, except for the ones stopping it
+struct Controls {
+    rx: Receiver<Control>,
+    deferred: VecDeque<Control>,
+    stopping: Option<Stopped>,
 }
 
-fn handler(event: Event, config: &mut Config, child_proc: &mut Option<Child>) {
-    if let Some(mut child) = child_proc.take() {
-        let _ = child.kill();
-        let _ = child.wait();
+impl Controls {
+    fn next(&mut self) -> Option<Control> {
+        self.deferred
+            .pop_front()
+            .or_else(|| self.rx.try_recv().ok())
     }
 
-    if should_reload_config(&event) {
-        match Config::new() {
-            Ok(new_config) => *config = new_config,
-            Err(err) => eprintln!("Error loading new config: {:?}", err),
+    // true if asked to stop, be it for the first time or again
+    fn receive(&mut self) -> bool {
+        let mut stop = false;
+        while let Ok(control) = self.rx.try_recv() {
+            match control.stops() {
+                Some(stopped) => {
+                    self.stopping.get_or_insert(stopped);
+                    stop = true;
+                }
+                None => self.deferred.push_back(control),
+            }
         }
+        stop
     }
+}
 
-    *child_proc = exec(config, true);
+// an exec_pre command is stopped as well when watchdog is
+fn wait_pre(process: &mut dyn Process, controls: &mut Controls) -> Option<ExitStatus> {
+    loop {
+        if controls.receive() || controls.stopping.is_some() {
+            return stop_gracefully(process, controls);
+        }
+        match process.wait_timeout(CHILD_POLL_INTERVAL) {
+            Ok(Some(status)) => return Some(status),
+            Ok(None) => {}
+            Err(_) => return None,
+        }
+    }
 }
 
-fn should_reload_config(event: &Event) -> bool {
-    let watchdog = PathBuf::from("watchdog.toml");
-    event
-        .paths
-        .iter()
-        .any(|path| path.file_name().unwrap().eq(watchdog.file_name().unwrap()))
+// terminates the process, then kills it if it's still running after STOP_TIMEOUT or when asked
+// to stop again meanwhile, e.g by pressing ctrl-c twice
+fn stop_gracefully(process: &mut dyn Process, controls: &mut Controls) -> Option<ExitStatus> {
+    if process.terminate().is_ok() {
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while Instant::now() < deadline && !controls.receive() {
+            match process.wait_timeout(CHILD_POLL_INTERVAL) {
+                Ok(Some(status)) => return Some(status),
+                Ok(None) => {}
+                Err(_) => break,
+            }
+        }
+    }
+    let _ = process.kill();
+    process.wait().ok()
 }
 
-fn event_handler(event: Event, config: &mut Config, child_proc: &mut Option<Child>) {
+// whether a file event passes the [watch] and ignore rules, shared by the runners
+pub(crate) fn triggers_run(
+    config: &Config,
+    event: &Event,
+    self_exe: Option<&SelfExe>,
+    content_hashes: &mut ContentHashes,
+) -> bool {
+    // config files are reloaded whatever `events` says, though not when they're merely read
+    let watched = match should_reload_config(config, event) {
+        true => !matches!(event.kind, EventKind::Access(_)),
+        false => config.to_watch().triggers(&event.kind),
+    };
+    if !watched
+        || is_self_build_output(config, event, self_exe)
+        || event.paths.contains(&pause_file(config))
+    {
+        return false;
+    }
+
     let should_ignore = match &event.kind {
         EventKind::Create(create_kind) => {
-            should_ignore_event(config, &event, create_kind == &CreateKind::Folder)
+            should_ignore_event(config, event, create_kind == &CreateKind::Folder)
         }
         EventKind::Remove(remove_kind) => {
-            should_ignore_event(config, &event, remove_kind == &RemoveKind::Folder)
+            should_ignore_event(config, event, remove_kind == &RemoveKind::Folder)
+        }
+        EventKind::Modify(ModifyKind::Name(_)) => {
+            let is_dir = judged_paths(event).iter().all(|path| path.is_dir());
+            should_ignore_event(config, event, is_dir)
         }
-        EventKind::Modify(_) => should_ignore_event(config, &event, false),
-        _ => true,
+        _ => should_ignore_event(config, event, false),
     };
-    if !should_ignore {
-        handler(event, config, child_proc);
+    !should_ignore && !is_content_unchanged(config, event, content_hashes)
+}
+
+// the command as watchdog runs it, from its origin and knowing the root it's being run for
+pub(crate) fn command(program: &str, args: &[String], origin: &Path, root: &Path) -> Command {
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .current_dir(origin)
+        .env(WATCHDOG_ROOT, root);
+    command
+}
+
+// extended config files can live outside of the root, their directories are watched as well
+// (rather than the files themselves, which atomic saves replace) so editing them reloads the config
+pub(crate) fn watch_config_dirs(config: &Config, mut watch: impl FnMut(&Path) -> Result<()>) {
+    config
+        .config_files()
+        .iter()
+        .filter(|config_file| !config_file.starts_with(config.root()))
+        .filter_map(|config_file| config_file.parent())
+        .for_each(|dir| {
+            if let Err(err) = watch(dir) {
+                eprintln!("Could not watch {dir:?}: {err}");
+            }
+        });
+}
+
+// the CLI adds to the ignore rules of the config files, so show what they ended up being
+pub(crate) fn print_banner(config: &Config) {
+    match config.profile() {
+        Some(profile) => println!("watching {:?} with profile {profile:?}", config.root()),
+        None => println!("watching {:?}", config.root()),
+    }
+    println!("  exclude: {:?}", config.to_exclude().to_exclude_files());
+    let extendables: Vec<String> = config
+        .to_extend()
+        .to_extendables()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    println!("  extend: {extendables:?}");
+}
+
+pub(crate) fn should_reload_config(config: &Config, event: &Event) -> bool {
+    event
+        .paths
+        .iter()
+        .any(|path| config.config_files().contains(path) || config.becomes_config_file(path))
+}
+
+fn pause_file(config: &Config) -> PathBuf {
+    config.root().join(PAUSE_FILE)
+}
+
+fn is_self_build_output(config: &Config, event: &Event, self_exe: Option<&SelfExe>) -> bool {
+    config.to_watch().reexec()
+        && self_exe.is_some_and(|self_exe| {
+            judged_paths(event)
+                .iter()
+                .all(|path| self_exe.is_build_output(path))
+        })
+}
+
+fn is_content_unchanged(
+    config: &Config,
+    event: &Event,
+    content_hashes: &mut ContentHashes,
+) -> bool {
+    if !config.to_watch().hash_contents() {
+        return false;
+    }
+
+    match &event.kind {
+        EventKind::Modify(_) => {
+            let judged_paths = judged_paths(event);
+            // the source of a rename doesn't exist anymore
+            event
+                .paths
+                .iter()
+                .filter(|path| !judged_paths.contains(path))
+                .for_each(|path| content_hashes.remove(path));
+
+            // every path has to be rehashed to keep the cache up to date, so don't short-circuit
+            let mut unchanged = true;
+            for path in judged_paths {
+                unchanged &= content_hashes.is_unchanged(path);
+            }
+            unchanged
+        }
+        EventKind::Create(_) => {
+            event
+                .paths
+                .iter()
+                .for_each(|path| content_hashes.update(path));
+            false
+        }
+        EventKind::Remove(_) => {
+            event
+                .paths
+                .iter()
+                .for_each(|path| content_hashes.remove(path));
+            false
+        }
+        _ => false,
     }
 }
 
 fn should_ignore_event(config: &Config, event: &Event, is_dir: bool) -> bool {
-    let paths = &event.paths;
+    judged_paths(event)
+        .iter()
+        .all(|path| should_ignore_path(config, path, is_dir))
+}
+
+pub fn should_ignore_path(config: &Config, path: &Path, is_dir: bool) -> bool {
+    ignore_reason(config, path, is_dir).is_some()
+}
+
+pub fn ignore_reason(config: &Config, path: &Path, is_dir: bool) -> Option<String> {
+    // only the directories of config files are watched outside of the root
+    if !path.starts_with(config.root()) && !config.config_files().iter().any(|file| file == path) {
+        return Some("outside of the root project".to_owned());
+    }
+
     let paths_to_ignore = config.to_exclude().to_exclude_files();
     let extendables = &config.to_extend().to_extendables();
-    paths.iter().all(|path| {
-        for path_to_ignore in paths_to_ignore {
-            if path.to_str().unwrap().contains(path_to_ignore) {
-                return true;
-            }
+    for path_to_ignore in paths_to_ignore {
+        if path.to_str().unwrap().contains(path_to_ignore) {
+            return Some(format!("excluded by {path_to_ignore:?}"));
         }
-        extendables
-            .iter()
-            .any(|extendable| extendable.matcher(path, is_dir))
-    })
+    }
+    extendables
+        .iter()
+        .find_map(|extendable| extendable.explain(path, is_dir))
 }
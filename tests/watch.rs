@@ -0,0 +1,546 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use tempfile::TempDir;
+use watchdog::{
+    testing::{
+        create, modify, remove, rename, rename_from, FakeEventSource, FakeSpawner, Recorder,
+    },
+    Config, Control, Controller, Stopped, Watchdog, WatchdogEvent,
+};
+
+// a root project with the given watchdog.toml, without clearing the screen nor debouncing
+fn project(config: &str) -> (TempDir, PathBuf) {
+    project_watching(config, "")
+}
+
+// the same, with more [watch] settings
+fn project_watching(config: &str, watch: &str) -> (TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let root = fs::canonicalize(dir.path()).unwrap();
+    fs::write(
+        root.join("watchdog.toml"),
+        format!("{config}\n[watch]\nclear = false\ndebounce = 0\n{watch}"),
+    )
+    .unwrap();
+    (dir, root)
+}
+
+fn run(root: &PathBuf, events: FakeEventSource) -> (FakeSpawner, Vec<WatchdogEvent>) {
+    let config = Config::builder().root(root).build().unwrap();
+    let (spawner, result, events) = run_controlled(config, events, |_| {});
+
+    // the fake event source disconnects once every batch was handed out
+    assert!(result.is_err());
+    (spawner, events)
+}
+
+// runs a Watchdog on the fakes, `control` being handed its controller before it starts
+fn run_controlled(
+    config: Config,
+    events: FakeEventSource,
+    control: impl FnOnce(&Controller),
+) -> (FakeSpawner, notify::Result<Stopped>, Vec<WatchdogEvent>) {
+    let spawner = FakeSpawner::new();
+    let recorder = Recorder::new();
+    let watchdog = Watchdog::new(config)
+        .event_source(events)
+        .spawner(spawner.clone())
+        .observe(recorder.clone());
+    control(&watchdog.controller());
+
+    let result = watchdog.run();
+    (spawner, result, recorder.events())
+}
+
+fn batches(events: &[WatchdogEvent]) -> Vec<Vec<PathBuf>> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            WatchdogEvent::BatchReceived { paths } => Some(paths.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn restarts_once_for_a_created_file() {
+    let (_dir, root) = project("[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n");
+    let events = FakeEventSource::new().batch([create(root.join("src/a.rs"))]);
+
+    let (spawner, events) = run(&root, events);
+
+    assert_eq!(batches(&events), [[root.join("src/a.rs")]]);
+    assert_eq!(spawner.command_lines(), ["cargo run", "cargo run"]);
+    assert!(events.iter().any(|event| matches!(
+        event,
+        WatchdogEvent::CommandExited { status: Some(status), pre: false, .. } if !status.success()
+    )));
+}
+
+#[test]
+fn stops_the_command_when_the_file_watcher_stops() {
+    let (_dir, root) = project("[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n");
+
+    let (spawner, events) = run(&root, FakeEventSource::new());
+
+    assert_eq!(spawner.command_lines(), ["cargo run"]);
+    assert!(matches!(
+        events.as_slice(),
+        [
+            WatchdogEvent::CommandStarted { .. },
+            WatchdogEvent::CommandExited { status: Some(status), pre: false, .. },
+        ] if !status.success()
+    ));
+}
+
+#[test]
+fn ignores_excluded_and_drained_events() {
+    let (_dir, root) = project(
+        "[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n\n[exclude]\nfiles = [ { path = \"target\" } ]\n",
+    );
+    let events = FakeEventSource::new()
+        .batch([modify(root.join("target/debug/app"))])
+        // the save of a.rs triggers a run, b.rs comes within the debounce time
+        .batch([
+            rename(root.join("src/.a.rs.swp"), root.join("src/a.rs")),
+            modify(root.join("src/b.rs")),
+        ])
+        .batch([modify(root.join("src/c.rs"))]);
+
+    let (spawner, events) = run(&root, events);
+
+    assert_eq!(
+        batches(&events),
+        [
+            vec![root.join("src/.a.rs.swp"), root.join("src/a.rs")],
+            vec![root.join("src/c.rs")],
+        ]
+    );
+    assert_eq!(spawner.command_lines().len(), 3);
+}
+
+#[test]
+fn restarts_once_a_file_is_moved_out_of_the_root() {
+    let (_dir, root) = project("[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n");
+    let events = FakeEventSource::new().batch([rename_from(root.join("src/a.rs"))]);
+
+    let (spawner, events) = run(&root, events);
+
+    assert_eq!(batches(&events), [[root.join("src/a.rs")]]);
+    assert_eq!(spawner.command_lines(), ["cargo run", "cargo run"]);
+}
+
+#[test]
+fn a_file_moved_out_of_the_root_is_a_rename() {
+    let moved_out = |events: &str| {
+        let (_dir, root) = project_watching(
+            "[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n",
+            &format!("events = [ {events} ]\n"),
+        );
+        let events = FakeEventSource::new().batch([rename_from(root.join("src/a.rs"))]);
+        run(&root, events).0.command_lines().len()
+    };
+
+    assert_eq!(moved_out("\"rename\""), 2);
+    assert_eq!(moved_out("\"remove\""), 1);
+}
+
+#[test]
+fn runs_every_event_paired_with_a_rename() {
+    let (_dir, root) = project("[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n");
+    // the rename-from is flushed as a move out of the root by the unrelated event that follows
+    let events = FakeEventSource::new().batch([
+        rename_from(root.join("src/a.rs")),
+        create(root.join("src/b.rs")),
+    ]);
+
+    let (spawner, events) = run(&root, events);
+
+    assert_eq!(
+        batches(&events),
+        [[root.join("src/a.rs")], [root.join("src/b.rs")]]
+    );
+    assert_eq!(spawner.command_lines().len(), 3);
+}
+
+#[test]
+fn reloads_the_config_and_restarts_with_it() {
+    let (_dir, root) = project("[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n");
+    let config = Config::builder().root(&root).build().unwrap();
+    fs::write(
+        root.join("watchdog.toml"),
+        "[exec]\ncommand = \"cargo\"\nargs = [ \"test\" ]\n\n[watch]\nclear = false\ndebounce = 0\n",
+    )
+    .unwrap();
+    let events = FakeEventSource::new().batch([modify(root.join("watchdog.toml"))]);
+
+    let (spawner, _, events) = run_controlled(config, events, |_| {});
+
+    assert!(events.contains(&WatchdogEvent::ConfigReloaded { restart: true }));
+    assert_eq!(spawner.command_lines(), ["cargo run", "cargo test"]);
+}
+
+#[test]
+fn reloads_the_config_whatever_events_are_watched() {
+    let (_dir, root) = project_watching(
+        "[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n",
+        "events = [ \"create\" ]\n",
+    );
+    let config = Config::builder().root(&root).build().unwrap();
+    fs::write(
+        root.join("watchdog.toml"),
+        "[exec]\ncommand = \"cargo\"\nargs = [ \"test\" ]\n\n[watch]\nclear = false\ndebounce = 0\nevents = [ \"create\" ]\n",
+    )
+    .unwrap();
+    let events = FakeEventSource::new()
+        .batch([modify(root.join("src/a.rs"))])
+        .batch([modify(root.join("watchdog.toml"))]);
+
+    let (spawner, _, events) = run_controlled(config, events, |_| {});
+
+    assert_eq!(batches(&events), [[root.join("watchdog.toml")]]);
+    assert_eq!(spawner.command_lines(), ["cargo run", "cargo test"]);
+}
+
+// an extended and a global config file, both outside of the root
+#[test]
+fn reloads_the_config_when_a_file_it_builds_on_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let dir = fs::canonicalize(dir.path()).unwrap();
+    let (root, base) = (dir.join("project"), dir.join("base.toml"));
+    let global = dir.join("global.toml");
+    fs::create_dir(&root).unwrap();
+    fs::write(
+        root.join("watchdog.toml"),
+        "extends = [ \"../base.toml\" ]\n\n[watch]\nclear = false\ndebounce = 0\n",
+    )
+    .unwrap();
+    fs::write(
+        &global,
+        "[exec_pre]\ncommands = [ { command = \"make\" } ]\n",
+    )
+    .unwrap();
+    fs::write(&base, "[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n").unwrap();
+    let config = Config::builder()
+        .root(&root)
+        .global_config_file(&global)
+        .build()
+        .unwrap();
+    fs::write(&base, "[exec]\ncommand = \"cargo\"\nargs = [ \"test\" ]\n").unwrap();
+
+    let events = FakeEventSource::new()
+        .batch([modify(base.clone())])
+        .batch([modify(global.clone())]);
+
+    let (spawner, _, events) = run_controlled(config, events, |_| {});
+
+    // the global file is still the same, reloading it has nothing to restart
+    let reloads: Vec<bool> = events
+        .into_iter()
+        .filter_map(|event| match event {
+            WatchdogEvent::ConfigReloaded { restart } => Some(restart),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(reloads, [true, false]);
+    assert_eq!(
+        spawner.command_lines(),
+        ["make", "cargo run", "make", "cargo test"]
+    );
+}
+
+#[test]
+fn runs_pre_commands_in_order_before_every_run() {
+    let (_dir, root) = project(
+        "[exec]\ncommand = \"app\"\n\n[exec_pre]\nwhen = \"always\"\ncommands = [\n  { command = \"cargo\", args = [ \"fmt\" ] },\n  { command = \"cargo\", args = [ \"build\" ] },\n]\n",
+    );
+    let events = FakeEventSource::new().batch([create(root.join("src/a.rs"))]);
+
+    let (spawner, events) = run(&root, events);
+
+    assert_eq!(
+        spawner.command_lines(),
+        [
+            "cargo fmt",
+            "cargo build",
+            "app",
+            "cargo fmt",
+            "cargo build",
+            "app"
+        ]
+    );
+    let sequence: Vec<String> = events
+        .iter()
+        .filter_map(|event| match event {
+            WatchdogEvent::CommandStarted { command, args, pre } => {
+                Some(format!("start {command} {args:?} pre={pre}"))
+            }
+            WatchdogEvent::CommandExited { command, pre, .. } => {
+                Some(format!("exit {command} pre={pre}"))
+            }
+            WatchdogEvent::BatchReceived { .. } => Some("batch".to_owned()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        sequence,
+        [
+            "start cargo [\"fmt\"] pre=true",
+            "exit cargo pre=true",
+            "start cargo [\"build\"] pre=true",
+            "exit cargo pre=true",
+            "start app [] pre=false",
+            "batch",
+            "exit app pre=false",
+            "start cargo [\"fmt\"] pre=true",
+            "exit cargo pre=true",
+            "start cargo [\"build\"] pre=true",
+            "exit cargo pre=true",
+            "start app [] pre=false",
+            // stopped once the fake event source disconnects
+            "exit app pre=false",
+        ]
+    );
+}
+
+// each event is emitted as it happens, with the commands spawned so far; a command that
+// can't be spawned is skipped
+#[test]
+fn reports_pre_commands_as_they_run() {
+    let (_dir, root) = project(
+        "[exec]\ncommand = \"app\"\n\n[exec_pre]\ncommands = [\n  { command = \"make\" },\n  { command = \"cargo\", args = [ \"fmt\" ] },\n  { command = \"cargo\", args = [ \"build\" ] },\n]\n",
+    );
+    let config = Config::builder().root(&root).build().unwrap();
+    let spawner = FakeSpawner::new().failing("make");
+    let observed = Arc::new(Mutex::new(Vec::new()));
+    let (spawned, seen) = (spawner.clone(), observed.clone());
+    let _ = Watchdog::new(config)
+        .event_source(FakeEventSource::new())
+        .spawner(spawner.clone())
+        .observe(move |event: &WatchdogEvent| {
+            let spawned = spawned.command_lines().len();
+            let event = match event {
+                WatchdogEvent::CommandStarted { command, .. } => format!("start {command}"),
+                WatchdogEvent::CommandExited { command, .. } => format!("exit {command}"),
+                _ => return,
+            };
+            seen.lock()
+                .unwrap()
+                .push(format!("{event} after {spawned}"));
+        })
+        .run();
+
+    assert_eq!(spawner.command_lines(), ["cargo fmt", "cargo build", "app"]);
+    assert_eq!(
+        *observed.lock().unwrap(),
+        [
+            "start cargo after 1",
+            "exit cargo after 1",
+            "start cargo after 2",
+            "exit cargo after 2",
+            "start app after 3",
+            "exit app after 3",
+        ]
+    );
+}
+
+#[test]
+fn runs_once_pre_commands_only_on_start() {
+    let (_dir, root) = project(
+        "[exec]\ncommand = \"app\"\n\n[exec_pre]\nwhen = \"once\"\ncommands = [ { command = \"make\" } ]\n",
+    );
+    let events = FakeEventSource::new()
+        .batch([create(root.join("a.txt"))])
+        .batch([create(root.join("b.txt"))]);
+
+    let (spawner, _) = run(&root, events);
+
+    assert_eq!(spawner.command_lines(), ["make", "app", "app", "app"]);
+}
+
+#[test]
+fn restarts_on_control_and_quits_gracefully() {
+    let (_dir, root) = project("[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n");
+    let config = Config::builder().root(&root).build().unwrap();
+    let events = FakeEventSource::new().batch([create(root.join("src/a.rs"))]);
+    let mut kept = None;
+
+    let (spawner, result, events) = run_controlled(config, events, |controller| {
+        controller.send(Control::Restart);
+        controller.send(Control::Quit);
+        kept = Some(controller.clone());
+    });
+
+    // quitting comes before the file events are looked at
+    assert!(result.is_ok());
+    assert!(!kept.unwrap().send(Control::Restart));
+    assert_eq!(spawner.command_lines(), ["cargo run", "cargo run"]);
+    let exits = events
+        .iter()
+        .filter(|event| matches!(event, WatchdogEvent::CommandExited { .. }))
+        .count();
+    assert_eq!(exits, 2);
+}
+
+#[test]
+fn ignores_changes_while_paused() {
+    let (_dir, root) = project("[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n");
+    let config = Config::builder().root(&root).build().unwrap();
+    let events = FakeEventSource::new().batch([create(root.join("src/a.rs"))]);
+
+    let (spawner, result, _) = run_controlled(config, events, |controller| {
+        controller.send(Control::TogglePause);
+    });
+
+    assert!(result.is_err());
+    assert_eq!(spawner.command_lines(), ["cargo run"]);
+}
+
+#[test]
+fn runs_once_on_resume_with_the_changes_made_while_paused() {
+    let (_dir, root) = project_watching(
+        "[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n",
+        "run_on_resume = true\n",
+    );
+    let config = Config::builder().root(&root).build().unwrap();
+    let events = FakeEventSource::new()
+        .batch([modify(root.join("src/a.rs"))])
+        .batch([modify(root.join("src/b.rs")), modify(root.join("src/a.rs"))])
+        // the pause file doesn't exist, so its removal resumes watching
+        .batch([remove(root.join(".watchdog-pause"))]);
+
+    let (spawner, result, events) = run_controlled(config, events, |controller| {
+        controller.send(Control::Pause);
+    });
+
+    assert!(result.is_err());
+    let changed = vec![root.join("src/a.rs"), root.join("src/b.rs")];
+    assert_eq!(spawner.command_lines(), ["cargo run", "cargo run"]);
+    assert!(events.contains(&WatchdogEvent::Resumed {
+        paths: changed.clone()
+    }));
+    assert_eq!(batches(&events), [changed]);
+}
+
+#[test]
+fn pauses_while_the_pause_file_exists() {
+    let (_dir, root) = project("[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n");
+    fs::write(root.join(".watchdog-pause"), "").unwrap();
+    let events = FakeEventSource::new()
+        .batch([create(root.join(".watchdog-pause"))])
+        .batch([modify(root.join("src/a.rs"))]);
+
+    let (spawner, events) = run(&root, events);
+
+    // without run_on_resume, resuming doesn't run the missed changes either
+    assert_eq!(spawner.command_lines(), ["cargo run"]);
+    assert_eq!(
+        events
+            .iter()
+            .filter(|event| **event == WatchdogEvent::Paused)
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn pauses_and_reloads_for_drained_events() {
+    let (_dir, root) = project("[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n");
+    let config = Config::builder().root(&root).build().unwrap();
+    fs::write(
+        root.join("watchdog.toml"),
+        "[exec]\ncommand = \"cargo\"\nargs = [ \"test\" ]\n\n[watch]\nclear = false\ndebounce = 0\n",
+    )
+    .unwrap();
+    // the changes after a.rs come within the debounce time
+    let events = FakeEventSource::new()
+        .batch([
+            create(root.join("src/a.rs")),
+            modify(root.join("watchdog.toml")),
+            create(root.join(".watchdog-pause")),
+        ])
+        .batch([modify(root.join("src/b.rs"))]);
+    let spawner = FakeSpawner::new();
+    let recorder = Recorder::new();
+    let pause_file = root.join(".watchdog-pause");
+
+    let result = Watchdog::new(config)
+        .event_source(events)
+        .spawner(spawner.clone())
+        .observe(recorder.clone())
+        .observe(move |event: &WatchdogEvent| {
+            if matches!(event, WatchdogEvent::BatchReceived { .. }) {
+                fs::write(&pause_file, "").unwrap();
+            }
+        })
+        .run();
+
+    let events = recorder.events();
+    assert!(result.is_err());
+    assert_eq!(batches(&events), [[root.join("src/a.rs")]]);
+    assert!(events.contains(&WatchdogEvent::Paused));
+    assert!(events.contains(&WatchdogEvent::ConfigReloaded { restart: true }));
+    assert_eq!(
+        spawner.command_lines(),
+        ["cargo run", "cargo run", "cargo test"]
+    );
+}
+
+#[test]
+fn stops_pre_commands_and_exits_with_the_signal_code() {
+    let (_dir, root) = project(
+        "[exec]\ncommand = \"app\"\n\n[exec_pre]\ncommands = [ { command = \"make\" }, { command = \"make\", args = [ \"assets\" ] } ]\n",
+    );
+    let config = Config::builder().root(&root).build().unwrap();
+    let events = FakeEventSource::new().batch([create(root.join("src/a.rs"))]);
+
+    let (spawner, result, events) = run_controlled(config, events, |controller| {
+        controller.send(Control::Terminate);
+    });
+
+    let stopped = result.unwrap();
+
+    assert_eq!(stopped, Stopped::Terminated);
+    assert_eq!(stopped.exit_code(), 143);
+    // neither the following pre commands nor the command itself are started
+    assert_eq!(spawner.command_lines(), ["make"]);
+    assert!(events.iter().any(|event| matches!(
+        event,
+        WatchdogEvent::CommandExited { status: Some(status), pre: true, .. } if !status.success()
+    )));
+}
+
+#[test]
+fn reloads_the_config_on_control() {
+    let (_dir, root) = project("[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n");
+    let config = Config::builder().root(&root).build().unwrap();
+    fs::write(
+        root.join("watchdog.toml"),
+        "[exec]\ncommand = \"cargo\"\nargs = [ \"test\" ]\n\n[watch]\nclear = false\ndebounce = 0\n",
+    )
+    .unwrap();
+
+    let (spawner, result, _) = run_controlled(config, FakeEventSource::new(), |controller| {
+        controller.send(Control::ReloadConfig);
+        controller.send(Control::Interrupt);
+    });
+
+    assert_eq!(result.unwrap().exit_code(), 130);
+    assert_eq!(spawner.command_lines(), ["cargo run", "cargo test"]);
+}
+
+#[test]
+fn resumes_once_the_pause_file_is_moved_out_of_the_root() {
+    let (_dir, root) = project("[exec]\ncommand = \"cargo\"\nargs = [ \"run\" ]\n");
+    let config = Config::builder().root(&root).build().unwrap();
+    let events = FakeEventSource::new().batch([rename_from(root.join(".watchdog-pause"))]);
+
+    let (_, _, events) = run_controlled(config, events, |controller| {
+        controller.send(Control::Pause);
+    });
+
+    assert!(events.contains(&WatchdogEvent::Resumed { paths: Vec::new() }));
+}